@@ -0,0 +1,103 @@
+#![allow(dead_code)]
+
+use s3ite::{Config, Sqlite};
+use s3s::auth::SimpleAuth;
+use s3s::service::S3ServiceBuilder;
+
+use std::fs;
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use aws_config::SdkConfig;
+use aws_credential_types::provider::SharedCredentialsProvider;
+use aws_sdk_s3::config::Credentials;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::types::{BucketLocationConstraint, CreateBucketConfiguration};
+use aws_sdk_s3::Client;
+
+pub const FS_ROOT: &str = concat!(env!("CARGO_TARGET_TMPDIR"), "/s3ite-bench");
+const DOMAIN_NAME: &str = "localhost:8014";
+const REGION: &str = "us-west-2";
+
+/// A service over an empty root with a client calling it in process, as in the test suite.
+pub struct BenchContext {
+    pub client: Client,
+    pub sqlite: Sqlite,
+}
+
+impl BenchContext {
+    pub async fn new(mut config: Config) -> Self {
+        let cred = Credentials::for_tests();
+
+        fs::remove_dir_all(FS_ROOT).ok();
+        fs::create_dir_all(FS_ROOT).unwrap();
+        config.root = FS_ROOT.into();
+        config.region = REGION.into();
+        config.background_maintenance = false;
+
+        let sqlite = Sqlite::new(&config).await.unwrap();
+
+        let service = {
+            let mut b = S3ServiceBuilder::new(sqlite.clone());
+            b.set_auth(SimpleAuth::from_single(
+                cred.access_key_id(),
+                cred.secret_access_key(),
+            ));
+            b.set_base_domain(DOMAIN_NAME);
+            b.build().into_shared()
+        };
+
+        let config = SdkConfig::builder()
+            .credentials_provider(SharedCredentialsProvider::new(cred))
+            .http_connector(s3s_aws::Connector::from(service))
+            .region(Region::new(REGION))
+            .endpoint_url(format!("http://{DOMAIN_NAME}"))
+            .build();
+
+        Self {
+            client: Client::new(&config),
+            sqlite,
+        }
+    }
+
+    pub async fn create_bucket(&self, bucket: &str) {
+        self.client
+            .create_bucket()
+            .create_bucket_configuration(
+                CreateBucketConfiguration::builder()
+                    .location_constraint(BucketLocationConstraint::from(REGION))
+                    .build(),
+            )
+            .bucket(bucket)
+            .send()
+            .await
+            .unwrap();
+    }
+}
+
+impl Deref for BenchContext {
+    type Target = Client;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl Drop for BenchContext {
+    fn drop(&mut self) {
+        fs::remove_dir_all(FS_ROOT).ok();
+    }
+}
+
+/// the mean duration of `iterations` sequential runs of `f`
+pub async fn mean<F, Fut>(iterations: u32, mut f: F) -> Duration
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f().await;
+    }
+    start.elapsed() / iterations
+}
@@ -0,0 +1,101 @@
+//! The latency of a `get_object` of a large object which is answered from its metadata, a
+//! conditional request returning `304 Not Modified` or a small range, compared to reading it all.
+//!
+//! ```bash
+//! cargo bench --bench get_object
+//! ```
+
+mod common;
+
+use common::{mean, BenchContext};
+use s3ite::Config;
+
+use aws_sdk_s3::primitives::ByteStream;
+
+const OBJECT_SIZE: usize = 64 * 1024 * 1024;
+const ITERATIONS: u32 = 20;
+
+#[tokio::main]
+async fn main() {
+    for blob_threshold in [None, Some(0)] {
+        let context = BenchContext::new(Config {
+            blob_threshold,
+            ..Default::default()
+        })
+        .await;
+
+        let bucket = "bench-get-object";
+        let key = "large.bin";
+        context.create_bucket(bucket).await;
+        let e_tag = context
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(vec![0; OBJECT_SIZE]))
+            .send()
+            .await
+            .unwrap()
+            .e_tag
+            .unwrap();
+
+        let full = mean(ITERATIONS, || async {
+            let output = context
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                output.body.collect().await.unwrap().into_bytes().len(),
+                OBJECT_SIZE
+            );
+        })
+        .await;
+
+        let not_modified = mean(ITERATIONS, || async {
+            let err = context
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .if_none_match(&e_tag)
+                .send()
+                .await
+                .unwrap_err();
+            assert_eq!(err.raw_response().unwrap().status().as_u16(), 304);
+        })
+        .await;
+
+        let range = mean(ITERATIONS, || async {
+            let output = context
+                .get_object()
+                .bucket(bucket)
+                .key(key)
+                .range("bytes=0-1023")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                output.body.collect().await.unwrap().into_bytes().len(),
+                1024
+            );
+        })
+        .await;
+
+        let storage = if blob_threshold.is_some() {
+            "file"
+        } else {
+            "database"
+        };
+        println!("get_object of a {OBJECT_SIZE} byte object stored in the {storage}:");
+        println!("  full:          {full:?}");
+        println!(
+            "  304:           {not_modified:?} ({:.1}x faster)",
+            full.as_secs_f64() / not_modified.as_secs_f64()
+        );
+        println!(
+            "  1 KiB range:   {range:?} ({:.1}x faster)",
+            full.as_secs_f64() / range.as_secs_f64()
+        );
+    }
+}
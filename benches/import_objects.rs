@@ -0,0 +1,63 @@
+//! The ingestion of many small objects by `Sqlite::import_objects`, which commits them in batches
+//! of `import_batch_size`, compared to one `put_object` each.
+//!
+//! ```bash
+//! cargo bench --bench import_objects
+//! ```
+
+mod common;
+
+use common::BenchContext;
+use s3ite::Config;
+
+use std::time::Instant;
+
+use aws_sdk_s3::primitives::ByteStream;
+
+const IMPORT_OBJECTS: usize = 100_000;
+const PUT_OBJECTS: usize = 10_000;
+
+#[tokio::main]
+async fn main() {
+    let context = BenchContext::new(Config::default()).await;
+
+    let bucket = "bench-import-objects";
+    context.create_bucket(bucket).await;
+
+    let start = Instant::now();
+    let imported = context
+        .sqlite
+        .import_objects(
+            bucket,
+            (0..IMPORT_OBJECTS)
+                .map(|i| (format!("import/{i:06}"), i.to_string().into_bytes(), None)),
+        )
+        .await
+        .unwrap();
+    let import_elapsed = start.elapsed();
+    assert_eq!(imported, IMPORT_OBJECTS);
+
+    let start = Instant::now();
+    for i in 0..PUT_OBJECTS {
+        context
+            .put_object()
+            .bucket(bucket)
+            .key(format!("put/{i:06}"))
+            .body(ByteStream::from(i.to_string().into_bytes()))
+            .send()
+            .await
+            .unwrap();
+    }
+    let put_elapsed = start.elapsed();
+
+    let import_per_object = import_elapsed / u32::try_from(IMPORT_OBJECTS).unwrap();
+    let put_per_object = put_elapsed / u32::try_from(PUT_OBJECTS).unwrap();
+    println!("import_objects: {IMPORT_OBJECTS} objects in {import_elapsed:?} ({import_per_object:?} per object)");
+    println!(
+        "put_object:     {PUT_OBJECTS} objects in {put_elapsed:?} ({put_per_object:?} per object)"
+    );
+    println!(
+        "import_objects is {:.1}x faster per object",
+        put_per_object.as_secs_f64() / import_per_object.as_secs_f64()
+    );
+}
@@ -0,0 +1,105 @@
+//! The memory held by SQLite for a bucket read by many concurrent requests with and without
+//! `shared_cache`, and what that costs concurrent writers which contend on table locks instead of
+//! waiting on the database file lock when the cache is shared.
+//!
+//! ```bash
+//! cargo bench --bench shared_cache
+//! ```
+
+mod common;
+
+use common::BenchContext;
+use s3ite::Config;
+
+use std::time::Instant;
+
+use aws_sdk_s3::primitives::ByteStream;
+use futures::future::join_all;
+
+const OBJECTS: usize = 1000;
+const OBJECT_SIZE: usize = 16 * 1024;
+const READERS: usize = 16;
+const WRITERS: usize = 16;
+
+/// the bytes currently allocated by SQLite across every connection of the process
+fn sqlite_memory_used() -> i64 {
+    // SAFETY: `sqlite3_memory_used` only reads a counter and may be called from any thread
+    unsafe { rusqlite::ffi::sqlite3_memory_used() }
+}
+
+#[tokio::main]
+async fn main() {
+    for shared_cache in [false, true] {
+        let context = BenchContext::new(Config {
+            shared_cache,
+            // every read must reach SQLite rather than being answered by the object cache
+            object_cache_capacity: 0,
+            ..Default::default()
+        })
+        .await;
+
+        let bucket = "bench-shared-cache";
+        context.create_bucket(bucket).await;
+        for i in 0..OBJECTS {
+            context
+                .put_object()
+                .bucket(bucket)
+                .key(format!("{i}.bin"))
+                .body(ByteStream::from(vec![0; OBJECT_SIZE]))
+                .send()
+                .await
+                .unwrap();
+        }
+
+        // each reader checks out its own connection which reads every page of the bucket
+        let before = sqlite_memory_used();
+        join_all((0..READERS).map(|_| async {
+            for i in 0..OBJECTS {
+                let output = context
+                    .get_object()
+                    .bucket(bucket)
+                    .key(format!("{i}.bin"))
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    output.body.collect().await.unwrap().into_bytes().len(),
+                    OBJECT_SIZE
+                );
+            }
+        }))
+        .await;
+        let memory = sqlite_memory_used() - before;
+
+        let start = Instant::now();
+        let results = join_all((0..WRITERS).map(|writer| {
+            let context = &context;
+            async move {
+                for i in 0..OBJECTS / WRITERS {
+                    let result = context
+                        .put_object()
+                        .bucket(bucket)
+                        .key(format!("{writer}/{i}.bin"))
+                        .body(ByteStream::from(vec![0; OBJECT_SIZE]))
+                        .send()
+                        .await;
+                    if let Err(err) = result {
+                        assert!(format!("{err:?}").contains("SlowDown"), "{err:?}");
+                        return 1;
+                    }
+                }
+                0
+            }
+        }))
+        .await;
+        let elapsed = start.elapsed();
+        let slow_downs: usize = results.iter().sum();
+
+        println!("shared_cache: {shared_cache}");
+        println!(
+            "  SQLite memory after {READERS} concurrent readers: {} KiB",
+            memory / 1024
+        );
+        println!("  {WRITERS} concurrent writers: {elapsed:?}, {slow_downs} stopped by SlowDown");
+    }
+}
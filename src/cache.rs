@@ -0,0 +1,141 @@
+use bytes::Bytes;
+use s3s::dto;
+use std::collections::{BTreeMap, HashMap};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedObject {
+    pub(crate) value: Bytes,
+    pub(crate) size: u64,
+    pub(crate) metadata: Option<dto::Metadata>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) storage_class: Option<String>,
+    pub(crate) last_modified: OffsetDateTime,
+    pub(crate) md5: Option<String>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    object: CachedObject,
+    tick: u64,
+}
+
+/// An in-memory least-recently-used cache of small objects keyed by `(bucket, key)`.
+///
+/// Every invalidation advances the `generation` so that a reader which fetched an object before a
+/// write was committed cannot repopulate the cache with the stale value afterwards.
+#[derive(Debug, Default)]
+pub(crate) struct ObjectCache {
+    capacity: usize,
+    max_size: u64,
+    max_object_size: u64,
+    size: u64,
+    tick: u64,
+    generation: u64,
+    entries: HashMap<(String, String), Entry>,
+    recency: BTreeMap<u64, (String, String)>,
+}
+
+impl ObjectCache {
+    pub(crate) fn new(capacity: usize, max_size: u64, max_object_size: u64) -> Self {
+        Self {
+            capacity,
+            max_size,
+            max_object_size,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn get(&mut self, bucket: &str, key: &str) -> Option<CachedObject> {
+        if self.capacity == 0 {
+            return None;
+        }
+
+        let cache_key = (bucket.to_string(), key.to_string());
+        let entry = self.entries.get_mut(&cache_key)?;
+
+        // mark as most recently used
+        self.tick += 1;
+        self.recency.remove(&entry.tick);
+        entry.tick = self.tick;
+        self.recency.insert(self.tick, cache_key);
+
+        Some(entry.object.clone())
+    }
+
+    /// insert an object read at `generation` unless the cache has been invalidated since
+    pub(crate) fn insert(
+        &mut self,
+        bucket: &str,
+        key: &str,
+        object: CachedObject,
+        generation: u64,
+    ) {
+        if self.capacity == 0
+            || generation != self.generation
+            || object.size > self.max_object_size
+            || object.size > self.max_size
+        {
+            return;
+        }
+
+        let cache_key = (bucket.to_string(), key.to_string());
+        self.remove(&cache_key);
+
+        // evict the least recently used objects until there is room
+        while self.entries.len() >= self.capacity || self.size + object.size > self.max_size {
+            let Some((_, evicted)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&evicted) {
+                self.size -= entry.object.size;
+            }
+        }
+
+        self.tick += 1;
+        self.size += object.size;
+        self.recency.insert(self.tick, cache_key.clone());
+        self.entries.insert(
+            cache_key,
+            Entry {
+                object,
+                tick: self.tick,
+            },
+        );
+    }
+
+    pub(crate) fn invalidate(&mut self, bucket: &str, key: &str) {
+        self.generation += 1;
+        self.remove(&(bucket.to_string(), key.to_string()));
+    }
+
+    pub(crate) fn invalidate_prefix(&mut self, bucket: &str, prefix: &str) {
+        self.generation += 1;
+        let cache_keys = self
+            .entries
+            .keys()
+            .filter(|(cache_bucket, cache_key)| {
+                cache_bucket == bucket && cache_key.starts_with(prefix)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for cache_key in cache_keys {
+            self.remove(&cache_key);
+        }
+    }
+
+    pub(crate) fn invalidate_bucket(&mut self, bucket: &str) {
+        self.invalidate_prefix(bucket, "");
+    }
+
+    fn remove(&mut self, cache_key: &(String, String)) {
+        if let Some(entry) = self.entries.remove(cache_key) {
+            self.recency.remove(&entry.tick);
+            self.size -= entry.object.size;
+        }
+    }
+}
@@ -1,14 +1,23 @@
+use crate::error::Result;
 use clap::ValueEnum;
+use http::header::{HeaderMap, HeaderName, HeaderValue};
+use s3s::S3Error;
+use s3s::S3ErrorCode::InternalError;
 use serde::Deserialize;
 use std::{
     collections::HashMap,
+    ffi::OsStr,
+    fs,
     net::{IpAddr, Ipv4Addr},
+    ops::Not,
     path::PathBuf,
     str::FromStr,
 };
+use tracing::warn;
 
 #[derive(Clone, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     /// The base path where the `.sqlite3` files will be created.
     /// All `.sqlite3` files at this path will be loaded at startup and exposed via this service.
@@ -23,12 +32,20 @@ pub struct Config {
     #[serde(default = "default_port")]
     pub port: u16,
 
+    /// The region reported by this service. A bucket can only be created with this location constraint.
+    #[serde(default = "default_region")]
+    pub region: String,
+
     /// The access key ID that is used to authenticate for this service.
     pub access_key: Option<String>,
 
     /// The secret access key that is used to authenticate for this service.
     pub secret_key: Option<String>,
 
+    /// The account id which owns the buckets of this service. A request with a different
+    /// `x-amz-expected-bucket-owner` is rejected.
+    pub owner_id: Option<String>,
+
     #[serde(default = "default_concurrency_limit")]
     /// Enforces a limit on the concurrent number of requests the underlying service can handle.
     /// This can be tuned depending on infrastructure as SSD/HDD will deal with resource contention very differently.
@@ -42,10 +59,150 @@ pub struct Config {
     /// The domain to use to allow parsing virtual-hosted-style requests.
     pub domain_name: Option<String>,
 
+    /// A file written once the service is listening and every bucket is open, for service managers
+    /// which wait for readiness.
+    pub ready_file: Option<PathBuf>,
+
     /// If this service should be read-only
     #[serde(default = "default_read_only")]
     pub read_only: bool,
 
+    /// Allow clients to preserve the timestamp of an imported object by supplying it as
+    /// RFC 3339 in the `x-amz-meta-s3ite-last-modified` header.
+    #[serde(default = "default_import_mode")]
+    pub import_mode: bool,
+
+    /// Return success instead of `BucketAlreadyExists` when creating a bucket that already exists.
+    #[serde(default = "default_idempotent_create_bucket")]
+    pub idempotent_create_bucket: bool,
+
+    /// The maximum number of buckets. `create_bucket` fails with `TooManyBuckets` once it is reached. Unset for no limit.
+    pub max_buckets: Option<usize>,
+
+    /// Delete a bucket and all of its objects instead of returning `BucketNotEmpty` when it still contains objects.
+    #[serde(default = "default_force_delete_bucket")]
+    pub force_delete_bucket: bool,
+
+    /// Strip a leading slash and collapse repeated slashes in object keys so that `/foo//bar` and `foo/bar` are the same object.
+    #[serde(default = "default_normalize_keys")]
+    pub normalize_keys: bool,
+
+    /// Reject a `put_object` to a key which already exists instead of overwriting the object.
+    #[serde(default = "default_no_overwrite")]
+    pub no_overwrite: bool,
+
+    /// Serve the index document set by `put_bucket_website` for a `get_object` of a key ending in `/`
+    /// and the error document for a `get_object` of a missing key.
+    #[serde(default = "default_website")]
+    pub website: bool,
+
+    /// Recompute the MD5 of an object read from a bucket and reject the read if it does not match the stored `ETag`.
+    #[serde(default = "default_verify_on_read")]
+    pub verify_on_read: bool,
+
+    /// Open the connections to each bucket in SQLite shared-cache mode so that they share one page cache.
+    /// Saves memory at the cost of write concurrency as lock conflicts return `SlowDown`.
+    #[serde(default = "default_shared_cache")]
+    pub shared_cache: bool,
+
+    /// Checkpoint the WAL of a bucket into its database file when the last connection to it closes.
+    /// Disable for a faster shutdown at the cost of replaying the WAL when the bucket is next opened.
+    #[serde(default = "default_checkpoint_on_close")]
+    pub checkpoint_on_close: bool,
+
+    /// Report a content type of `application/x-directory` for a `head_object` of a directory marker.
+    #[serde(default = "default_directory_content_type")]
+    pub directory_content_type: bool,
+
+    /// Record the time each object is first written, which is kept when it is overwritten, and return it from `head_object`.
+    #[serde(default = "default_track_created_at")]
+    pub track_created_at: bool,
+
+    /// Reject a configuration with a combination of SQLite pragmas that risks corrupting a bucket instead of logging a warning.
+    #[serde(default = "default_strict_pragmas")]
+    pub strict_pragmas: bool,
+
+    /// Create the tables in a `.sqlite3` file found in the root without them instead of refusing to start.
+    #[serde(default = "default_adopt_bare_buckets")]
+    pub adopt_bare_buckets: bool,
+
+    /// Run the maintenance process every ten seconds in the background. Disable to call
+    /// `Sqlite::run_maintenance` on another schedule when embedding this library.
+    #[serde(default = "default_background_maintenance")]
+    pub background_maintenance: bool,
+
+    /// Log a warning when the WAL of a bucket exceeds this size in bytes at a maintenance checkpoint. Set to `0` to disable.
+    #[serde(default = "default_wal_warning_size")]
+    pub wal_warning_size: u64,
+
+    /// Run a full `VACUUM` of a bucket at a maintenance pass once more than this percentage of its pages are free.
+    /// Unset to only reclaim free pages incrementally.
+    pub vacuum_freelist_percent: Option<u8>,
+
+    /// Log a warning when a request has to wait behind this many other requests for a connection to a bucket. Set to `0` to disable.
+    #[serde(default = "default_pool_warning_waiting")]
+    pub pool_warning_waiting: usize,
+
+    /// Close the connections to a bucket which has not been accessed for this many seconds at the next
+    /// maintenance pass. They are reopened on the next request. Unset to keep connections open.
+    pub idle_timeout: Option<u64>,
+
+    /// Objects larger than this size in bytes are stored as files in a `<bucket>.blobs` directory next to the
+    /// bucket database instead of inside it. Unset to store all objects in the database.
+    pub blob_threshold: Option<u64>,
+
+    /// The number of objects written in each transaction by `Sqlite::import_objects`.
+    #[serde(default = "default_import_batch_size")]
+    pub import_batch_size: usize,
+
+    /// Store the objects written by `Sqlite::import_objects` without an ETag and compute it in the
+    /// background maintenance process instead.
+    #[serde(default = "default_lazy_etags")]
+    pub lazy_etags: bool,
+
+    /// The highest part number of a multipart upload and the most parts it can be completed with.
+    #[serde(default = "default_max_parts")]
+    pub max_parts: u32,
+
+    /// The largest object in bytes accepted by `put_object` or assembled by `complete_multipart_upload`. Unset for no limit.
+    pub max_object_size: Option<u64>,
+
+    /// Reject an `upload_part` without a `Content-MD5` header so that every part is verified.
+    #[serde(default = "default_require_part_md5")]
+    pub require_part_md5: bool,
+
+    /// The maximum number of recently read objects to hold in memory. Set to `0` to disable the object cache.
+    #[serde(default = "default_object_cache_capacity")]
+    pub object_cache_capacity: usize,
+
+    /// The maximum total size in bytes of all objects held in the object cache.
+    #[serde(default = "default_object_cache_size")]
+    pub object_cache_size: u64,
+
+    /// Only objects up to this size in bytes will be held in the object cache.
+    #[serde(default = "default_object_cache_object_size")]
+    pub object_cache_object_size: u64,
+
+    /// The S3 operations, such as `GetObject`, each access key may perform. A trailing `*` matches
+    /// any operation with that prefix. Access keys without an entry may perform any operation.
+    #[serde(default = "HashMap::new")]
+    pub allowed_operations: HashMap<String, Vec<String>>,
+
+    /// Headers, such as `X-Content-Type-Options: nosniff`, added to every response which does not already set them.
+    #[serde(default = "HashMap::new")]
+    pub response_headers: HashMap<String, String>,
+
+    /// Additional SQLite pragmas, such as `cell_size_check=true`, applied to every bucket connection
+    /// as it is opened.
+    /// Only the pragmas in `EXTRA_PRAGMAS` may be set.
+    #[serde(default = "Vec::new")]
+    pub extra_pragmas: Vec<String>,
+
+    /// The number of times a write is retried when the bucket database is busy or locked by
+    /// another connection before a `SlowDown` error is returned.
+    #[serde(default = "default_busy_retries")]
+    pub busy_retries: u32,
+
     /// Service level SQLite configurations
     #[serde(flatten, default = "default_pragmas")]
     pub sqlite: Pragmas,
@@ -53,6 +210,10 @@ pub struct Config {
     /// Bucket specific configurations
     #[serde(default = "HashMap::new")]
     pub buckets: HashMap<String, Bucket>,
+
+    /// A directory of `<bucket>.yaml` files each containing a bucket specific configuration.
+    /// These take precedence over any configuration for the same bucket in `buckets`.
+    pub buckets_dir: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -61,19 +222,233 @@ impl Default for Config {
             root: default_root(),
             host: default_host(),
             port: default_port(),
+            region: default_region(),
             access_key: None,
             secret_key: None,
+            owner_id: None,
             concurrency_limit: default_concurrency_limit(),
             permissive_cors: default_permissive_cors(),
             read_only: default_read_only(),
+            import_mode: default_import_mode(),
+            idempotent_create_bucket: default_idempotent_create_bucket(),
+            max_buckets: None,
+            force_delete_bucket: default_force_delete_bucket(),
+            normalize_keys: default_normalize_keys(),
+            no_overwrite: default_no_overwrite(),
+            website: default_website(),
+            verify_on_read: default_verify_on_read(),
+            shared_cache: default_shared_cache(),
+            checkpoint_on_close: default_checkpoint_on_close(),
+            directory_content_type: default_directory_content_type(),
+            track_created_at: default_track_created_at(),
+            strict_pragmas: default_strict_pragmas(),
+            adopt_bare_buckets: default_adopt_bare_buckets(),
+            background_maintenance: default_background_maintenance(),
+            wal_warning_size: default_wal_warning_size(),
+            vacuum_freelist_percent: None,
+            pool_warning_waiting: default_pool_warning_waiting(),
+            idle_timeout: None,
+            blob_threshold: None,
             domain_name: None,
+            ready_file: None,
+            import_batch_size: default_import_batch_size(),
+            lazy_etags: default_lazy_etags(),
+            max_parts: default_max_parts(),
+            max_object_size: None,
+            require_part_md5: default_require_part_md5(),
+            object_cache_capacity: default_object_cache_capacity(),
+            object_cache_size: default_object_cache_size(),
+            object_cache_object_size: default_object_cache_object_size(),
+            extra_pragmas: Vec::default(),
+            busy_retries: default_busy_retries(),
             sqlite: default_pragmas(),
+            allowed_operations: HashMap::default(),
+            response_headers: HashMap::default(),
             buckets: HashMap::default(),
+            buckets_dir: None,
         }
     }
 }
 
 impl Config {
+    /// Merge the `<bucket>.yaml` files found in `buckets_dir` into `buckets`.
+    pub fn merge_buckets_dir(&mut self) -> Result<()> {
+        let Some(buckets_dir) = &self.buckets_dir else {
+            return Ok(());
+        };
+
+        for entry in fs::read_dir(buckets_dir)? {
+            let path = entry?.path();
+            if path.is_file().not() || path.extension() != Some(OsStr::new("yaml")) {
+                continue;
+            }
+
+            let Some(bucket) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let config = serde_yaml::from_slice::<Bucket>(&fs::read(&path)?)?;
+            if self.buckets.insert(bucket.to_string(), config).is_some() {
+                warn!("configuration for bucket: {bucket:?} in {path:?} overrides the main configuration");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the configuration reporting every problem found rather than only the first.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.root.is_dir().not() {
+            problems.push(format!("root: {:?} is not a directory", self.root));
+        }
+
+        // validate that any specified bucket configurations have existing bucket
+        let mut missing = self
+            .buckets
+            .keys()
+            .filter(|bucket| self.root.join(format!("{bucket}.sqlite3")).is_file().not())
+            .collect::<Vec<_>>();
+        missing.sort();
+        if missing.is_empty().not() {
+            problems.push(format!(
+                "found configurations for buckets: {missing:?} that do not exist"
+            ));
+        }
+
+        if self.access_key.is_some() != self.secret_key.is_some() {
+            problems.push("access_key and secret_key must be provided together".to_string());
+        }
+
+        if self.concurrency_limit == 0 {
+            problems.push("concurrency_limit must be greater than 0".to_string());
+        }
+
+        if self.read_only && self.import_mode {
+            problems.push("read_only cannot be combined with import_mode".to_string());
+        }
+
+        for (bucket, config) in &self.buckets {
+            let overridden = config.sqlite.as_ref().is_some_and(|sqlite| {
+                sqlite.journal_mode.is_some() || sqlite.synchronous.is_some()
+            });
+            if config.durable == Some(true) && overridden {
+                problems.push(format!(
+                    "{bucket}: durable cannot be combined with journal_mode or synchronous"
+                ));
+            }
+            let writes = config.no_overwrite == Some(true) || config.default_metadata.is_some();
+            if self.read_only(Some(bucket)) && writes {
+                problems.push(format!(
+                    "{bucket}: read_only cannot be combined with no_overwrite or default_metadata"
+                ));
+            }
+            if let Some(content_disposition) = &config.content_disposition {
+                if matches!(content_disposition.as_str(), "attachment" | "inline").not() {
+                    problems.push(format!(
+                        "{bucket}: content_disposition must be attachment or inline"
+                    ));
+                }
+            }
+        }
+
+        for (name, value) in &self.response_headers {
+            if parse_response_header(name, value).is_none() {
+                problems.push(format!(
+                    "response_headers: {name:?}: {value:?} is not a valid header name and value"
+                ));
+            }
+        }
+
+        for extra_pragma in &self.extra_pragmas {
+            if parse_extra_pragma(extra_pragma).is_none() {
+                problems.push(format!(
+                    "extra_pragmas: {extra_pragma:?} is not a `name=value` assignment of one of {EXTRA_PRAGMAS:?}"
+                ));
+            }
+        }
+
+        for warning in self.pragma_warnings() {
+            if self.strict_pragmas {
+                problems.push(warning);
+            } else {
+                warn!("{warning}");
+            }
+        }
+
+        if problems.is_empty().not() {
+            Err(S3Error::with_message(
+                InternalError,
+                format!("invalid configuration:\n- {}", problems.join("\n- ")),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Describe each combination of SQLite pragmas, at the service level or for a bucket, that risks corrupting a bucket.
+    #[must_use]
+    pub fn pragma_warnings(&self) -> Vec<String> {
+        let mut buckets = self.buckets.keys().map(String::as_str).collect::<Vec<_>>();
+        buckets.sort_unstable();
+
+        std::iter::once(("service", None))
+            .chain(buckets.into_iter().map(|bucket| (bucket, Some(bucket))))
+            .filter_map(|(scope, bucket)| {
+                let journal_mode = self.journal_mode(bucket);
+                let synchronous = self.synchronous(bucket);
+                (matches!(journal_mode, JournalMode::OFF | JournalMode::MEMORY)
+                    && synchronous == Synchronous::OFF)
+                    .then(|| {
+                        format!(
+                            "{scope}: journal_mode={journal_mode:?} with synchronous={synchronous:?} can corrupt the database if the process or host crashes"
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// If the bucket should use the `durable` pragmas of `journal_mode=WAL` and `synchronous=FULL`
+    #[must_use]
+    pub fn durable(&self, bucket: Option<&str>) -> bool {
+        bucket
+            .and_then(|bucket| self.buckets.get(bucket).and_then(|bucket| bucket.durable))
+            .unwrap_or(false)
+    }
+
+    #[must_use]
+    pub fn bucket_concurrency_limit(&self, bucket: &str) -> Option<u16> {
+        self.buckets
+            .get(bucket)
+            .and_then(|bucket| bucket.concurrency_limit)
+    }
+
+    #[must_use]
+    pub fn key_search(&self, bucket: Option<&str>) -> bool {
+        bucket
+            .and_then(|bucket| {
+                self.buckets
+                    .get(bucket)
+                    .and_then(|bucket| bucket.key_search)
+            })
+            .unwrap_or(false)
+    }
+
+    #[must_use]
+    pub fn default_metadata(&self, bucket: &str) -> Option<&HashMap<String, String>> {
+        self.buckets
+            .get(bucket)
+            .and_then(|bucket| bucket.default_metadata.as_ref())
+    }
+
+    #[must_use]
+    pub fn content_disposition(&self, bucket: &str) -> Option<&str> {
+        self.buckets
+            .get(bucket)
+            .and_then(|bucket| bucket.content_disposition.as_deref())
+    }
+
     #[must_use]
     pub fn read_only(&self, bucket: Option<&str>) -> bool {
         bucket
@@ -81,8 +456,59 @@ impl Config {
             .unwrap_or(self.read_only)
     }
 
+    /// The `response_headers` to add to every response. Invalid entries are skipped as they are
+    /// reported by `validate`.
+    #[must_use]
+    pub fn response_header_map(&self) -> HeaderMap {
+        self.response_headers
+            .iter()
+            .filter_map(|(name, value)| parse_response_header(name, value))
+            .collect()
+    }
+
+    /// If `access_key` may perform the S3 `operation` according to `allowed_operations`
+    #[must_use]
+    pub fn operation_allowed(&self, access_key: &str, operation: &str) -> bool {
+        self.allowed_operations
+            .get(access_key)
+            .map_or(true, |allowed_operations| {
+                allowed_operations.iter().any(|allowed_operation| {
+                    match allowed_operation.strip_suffix('*') {
+                        Some(prefix) => operation.starts_with(prefix),
+                        None => operation == allowed_operation,
+                    }
+                })
+            })
+    }
+
+    #[must_use]
+    pub fn no_overwrite(&self, bucket: Option<&str>) -> bool {
+        bucket
+            .and_then(|bucket| {
+                self.buckets
+                    .get(bucket)
+                    .and_then(|bucket| bucket.no_overwrite)
+            })
+            .unwrap_or(self.no_overwrite)
+    }
+
+    #[must_use]
+    pub fn blob_threshold(&self, bucket: Option<&str>) -> Option<u64> {
+        bucket
+            .and_then(|bucket| {
+                self.buckets
+                    .get(bucket)
+                    .and_then(|bucket| bucket.blob_threshold)
+            })
+            .or(self.blob_threshold)
+    }
+
     #[must_use]
     pub fn journal_mode(&self, bucket: Option<&str>) -> JournalMode {
+        if self.durable(bucket) {
+            return JournalMode::WAL;
+        }
+
         bucket
             .and_then(|bucket| {
                 self.buckets.get(bucket).and_then(|bucket| {
@@ -97,6 +523,10 @@ impl Config {
 
     #[must_use]
     pub fn synchronous(&self, bucket: Option<&str>) -> Synchronous {
+        if self.durable(bucket) {
+            return Synchronous::FULL;
+        }
+
         bucket
             .and_then(|bucket| {
                 self.buckets
@@ -128,23 +558,50 @@ impl Config {
             .unwrap_or(self.sqlite.cache_size)
     }
 
+    #[must_use]
+    pub fn busy_timeout(&self, bucket: Option<&str>) -> u32 {
+        bucket
+            .and_then(|bucket| {
+                self.buckets.get(bucket).and_then(|bucket| {
+                    bucket
+                        .sqlite
+                        .as_ref()
+                        .and_then(|sqlite| sqlite.busy_timeout)
+                })
+            })
+            .unwrap_or(self.sqlite.busy_timeout)
+    }
+
     #[must_use]
     pub fn to_sql(&self, bucket: Option<&str>) -> String {
+        // entries which are not valid are reported by `validate` and never reach the database
+        let extra_pragmas = self
+            .extra_pragmas
+            .iter()
+            .map(String::as_str)
+            .filter_map(parse_extra_pragma)
+            .map(|(name, value)| format!("PRAGMA {name}={value};"))
+            .collect::<String>();
+
         format!(
             "
             PRAGMA journal_mode={:?};
             PRAGMA synchronous={:?};
             PRAGMA temp_store={:?};
             PRAGMA cache_size=-{};
+            PRAGMA busy_timeout={};
             PRAGMA query_only={};
             PRAGMA foreign_keys=true;
             PRAGMA auto_vacuum=INCREMENTAL;
+            {}
         ",
             self.journal_mode(bucket),
             self.synchronous(bucket),
             self.temp_store(bucket),
             self.cache_size(bucket),
+            self.busy_timeout(bucket),
             self.read_only(bucket),
+            extra_pragmas,
         )
     }
 }
@@ -166,6 +623,10 @@ pub struct Pragmas {
     /// Controls the SQLite `cache_size` pragma in kilobytes.
     #[serde(default = "default_cache_size")]
     pub cache_size: u32,
+
+    /// Controls the SQLite `busy_timeout` pragma in milliseconds.
+    #[serde(default = "default_busy_timeout")]
+    pub busy_timeout: u32,
 }
 
 impl Default for Pragmas {
@@ -175,20 +636,43 @@ impl Default for Pragmas {
             synchronous: Synchronous::NORMAL,
             temp_store: TempStore::MEMORY,
             cache_size: 67_108_864,
+            busy_timeout: 5_000,
         }
     }
 }
 
-#[derive(Clone, Deserialize, Debug)]
+#[derive(Clone, Default, Deserialize, Debug)]
 pub struct Bucket {
     /// If this bucket should be read-only
     pub read_only: Option<bool>,
 
+    /// Shorthand for `journal_mode=WAL` and `synchronous=FULL` for a bucket holding critical data
+    pub durable: Option<bool>,
+
+    /// If a `put_object` to a key which already exists should be rejected
+    pub no_overwrite: Option<bool>,
+
+    /// Objects larger than this size in bytes are stored as files outside of the bucket database.
+    pub blob_threshold: Option<u64>,
+
+    /// The maximum number of S3 operations run concurrently against this bucket. Further requests wait for one to complete.
+    pub concurrency_limit: Option<u16>,
+
+    /// Maintain a full text index over the object keys of this bucket for `Sqlite::search_keys`
+    pub key_search: Option<bool>,
+
+    /// User metadata stored with every object uploaded to this bucket unless the client supplies the same key
+    pub default_metadata: Option<HashMap<String, String>>,
+
+    /// The `Content-Disposition` type, `attachment` or `inline`, of every object downloaded from this bucket with the
+    /// last segment of its key as the filename
+    pub content_disposition: Option<String>,
+
     /// Bucket level SQLite configurations
     pub sqlite: Option<BucketPragmas>,
 }
 
-#[derive(Clone, Deserialize, Debug)]
+#[derive(Clone, Default, Deserialize, Debug)]
 pub struct BucketPragmas {
     /// Controls the SQLite `journal_mode` flag pragma.
     pub journal_mode: Option<JournalMode>,
@@ -201,6 +685,9 @@ pub struct BucketPragmas {
 
     /// Controls the SQLite `cache_size` pragma in kilobytes.
     pub cache_size: Option<u32>,
+
+    /// Controls the SQLite `busy_timeout` pragma in milliseconds.
+    pub busy_timeout: Option<u32>,
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, ValueEnum)]
@@ -231,6 +718,52 @@ pub enum TempStore {
     MEMORY,
 }
 
+/// The SQLite pragmas which may be set with `extra_pragmas`.
+pub const EXTRA_PRAGMAS: &[&str] = &[
+    "automatic_index",
+    "cache_spill",
+    "cell_size_check",
+    "hard_heap_limit",
+    "journal_size_limit",
+    "mmap_size",
+    "recursive_triggers",
+    "secure_delete",
+    "soft_heap_limit",
+    "threads",
+    "trusted_schema",
+    "wal_autocheckpoint",
+];
+
+/// split an `extra_pragmas` entry, optionally prefixed with `PRAGMA`, into its name and value if
+/// the name is in `EXTRA_PRAGMAS` and the value is a plain word or number so that it cannot
+/// inject other statements
+fn parse_extra_pragma(extra_pragma: &str) -> Option<(&str, &str)> {
+    let extra_pragma = extra_pragma.trim();
+    let extra_pragma = extra_pragma
+        .get(..7)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("PRAGMA "))
+        .map_or(extra_pragma, |_| &extra_pragma[7..]);
+
+    let (name, value) = extra_pragma.split_once('=')?;
+    let (name, value) = (name.trim(), value.trim());
+
+    let valid_value = value.is_empty().not()
+        && value
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c.is_ascii_alphanumeric() || c == '_' || (i == 0 && c == '-'));
+
+    (EXTRA_PRAGMAS.contains(&name.to_ascii_lowercase().as_str()) && valid_value)
+        .then_some((name, value))
+}
+
+fn parse_response_header(name: &str, value: &str) -> Option<(HeaderName, HeaderValue)> {
+    Some((
+        HeaderName::from_bytes(name.as_bytes()).ok()?,
+        HeaderValue::from_str(value).ok()?,
+    ))
+}
+
 fn default_root() -> PathBuf {
     PathBuf::from_str(".").unwrap()
 }
@@ -243,6 +776,10 @@ fn default_port() -> u16 {
     8014
 }
 
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
 fn default_concurrency_limit() -> u16 {
     16
 }
@@ -255,6 +792,102 @@ fn default_read_only() -> bool {
     false
 }
 
+fn default_import_mode() -> bool {
+    false
+}
+
+fn default_idempotent_create_bucket() -> bool {
+    false
+}
+
+fn default_force_delete_bucket() -> bool {
+    false
+}
+
+fn default_normalize_keys() -> bool {
+    false
+}
+
+fn default_no_overwrite() -> bool {
+    false
+}
+
+fn default_website() -> bool {
+    false
+}
+
+fn default_verify_on_read() -> bool {
+    false
+}
+
+fn default_shared_cache() -> bool {
+    false
+}
+
+fn default_checkpoint_on_close() -> bool {
+    true
+}
+
+fn default_directory_content_type() -> bool {
+    false
+}
+
+fn default_track_created_at() -> bool {
+    false
+}
+
+fn default_strict_pragmas() -> bool {
+    false
+}
+
+fn default_adopt_bare_buckets() -> bool {
+    false
+}
+
+fn default_background_maintenance() -> bool {
+    true
+}
+
+fn default_wal_warning_size() -> u64 {
+    1_073_741_824
+}
+
+fn default_pool_warning_waiting() -> usize {
+    64
+}
+
+fn default_busy_retries() -> u32 {
+    3
+}
+
+fn default_import_batch_size() -> usize {
+    1_000
+}
+
+fn default_lazy_etags() -> bool {
+    false
+}
+
+fn default_max_parts() -> u32 {
+    10_000
+}
+
+fn default_require_part_md5() -> bool {
+    false
+}
+
+fn default_object_cache_capacity() -> usize {
+    0
+}
+
+fn default_object_cache_size() -> u64 {
+    67_108_864
+}
+
+fn default_object_cache_object_size() -> u64 {
+    1_048_576
+}
+
 fn default_pragmas() -> Pragmas {
     Pragmas::default()
 }
@@ -274,3 +907,7 @@ fn default_temp_store() -> TempStore {
 fn default_cache_size() -> u32 {
     67_108_864
 }
+
+fn default_busy_timeout() -> u32 {
+    5_000
+}
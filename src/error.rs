@@ -1,3 +1,5 @@
+use deadpool_sqlite::rusqlite::{self, ErrorCode};
+use deadpool_sqlite::PoolError;
 use s3s::S3Error;
 use s3s::S3ErrorCode;
 use s3s::StdError;
@@ -35,25 +37,54 @@ impl From<Error> for S3Error {
     fn from(e: Error) -> Self {
         match e.source.downcast::<S3Error>() {
             Ok(s3error) => *s3error,
-            Err(source) => S3Error::with_source(S3ErrorCode::InternalError, source),
+            Err(source) => S3Error::with_source(error_code(&*source), source),
         }
     }
 }
 
+/// map an internal error to the S3 error returned to the client keeping its message
+pub(crate) fn to_s3_error<E: std::error::Error + 'static>(err: E) -> S3Error {
+    S3Error::with_message(error_code(&err), err.to_string())
+}
+
+/// Choose the S3 error code for an internal error so that clients can retry intelligently:
+///
+/// - `SlowDown` (503) when SQLite reports the database is busy or locked by another connection.
+/// - `ServiceUnavailable` (503) when no connection to the bucket could be taken from its pool.
+/// - `InternalError` (500) for anything else.
+fn error_code(err: &(dyn std::error::Error + 'static)) -> S3ErrorCode {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<rusqlite::Error>() {
+            if matches!(
+                err.sqlite_error_code(),
+                Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+            ) {
+                return S3ErrorCode::SlowDown;
+            }
+        }
+        if let Some(PoolError::Timeout(_) | PoolError::Closed) = err.downcast_ref::<PoolError>() {
+            return S3ErrorCode::ServiceUnavailable;
+        }
+        source = err.source();
+    }
+    S3ErrorCode::InternalError
+}
+
+/// log an internal error under the `s3ite::error` target so that it can be filtered with
+/// `RUST_LOG` alongside the other events of this crate whether or not it is used as a library
 #[inline]
 #[track_caller]
 pub(crate) fn log(source: &dyn std::error::Error) {
-    if cfg!(feature = "binary") {
-        let location = Location::caller();
-        let span_trace = tracing_error::SpanTrace::capture();
+    let location = Location::caller();
+    let span_trace = tracing_error::SpanTrace::capture();
 
-        error!(
-            target: "s3s_fs_internal_error",
-            %location,
-            error=%source,
-            "span trace:\n{span_trace}"
-        );
-    }
+    error!(
+        target: "s3ite::error",
+        %location,
+        error=%source,
+        "span trace:\n{span_trace}"
+    );
 }
 
 macro_rules! try_ {
@@ -12,6 +12,7 @@
 #[macro_use]
 mod error;
 
+mod cache;
 mod config;
 mod s3;
 mod sqlite;
@@ -8,14 +8,17 @@ use s3s::auth::SimpleAuth;
 use s3s::service::S3ServiceBuilder;
 use tower::limit::ConcurrencyLimitLayer;
 use tower::make::Shared;
+use tower::util::MapResponseLayer;
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
 
+use std::env;
 use std::fs;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::net::TcpListener;
-use std::path::PathBuf;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use hyper::server::Server;
@@ -31,9 +34,17 @@ struct Opt {
     root: Option<PathBuf>,
 
     #[clap(long)]
-    /// The domain to use to allow parsing virtual-hosted-style requests.
+    /// The path to a `yaml` configuration file.
     config: Option<PathBuf>,
 
+    #[clap(long)]
+    /// A directory of `<bucket>.yaml` files each containing a bucket specific configuration.
+    buckets_dir: Option<PathBuf>,
+
+    #[clap(long)]
+    /// Validate the configuration and exit without starting the service.
+    check_config: bool,
+
     #[clap(long)]
     /// The IP address to listen on for this service. Use `0.0.0.0` to listen on all interfaces.
     host: Option<IpAddr>,
@@ -47,6 +58,10 @@ struct Opt {
     /// This can be enabled to allow users to access this service from a web service running on a different host.
     permissive_cors: Option<bool>,
 
+    #[clap(long)]
+    /// The region reported by this service. A bucket can only be created with this location constraint.
+    region: Option<String>,
+
     #[clap(long, requires = "secret_key")]
     /// The access key ID that is used to authenticate for this service.
     access_key: Option<String>,
@@ -55,10 +70,19 @@ struct Opt {
     /// The secret access key that is used to authenticate for this service.
     secret_key: Option<String>,
 
+    #[clap(long)]
+    /// The account id which owns the buckets of this service. A request with a different
+    /// `x-amz-expected-bucket-owner` is rejected.
+    owner_id: Option<String>,
+
     #[clap(long)]
     /// The domain to use to allow parsing virtual-hosted-style requests.
     domain_name: Option<String>,
 
+    #[clap(long)]
+    /// A file written once the service is listening and every bucket is open.
+    ready_file: Option<PathBuf>,
+
     #[clap(long)]
     /// Enforces a limit on the concurrent number of requests the underlying service can handle.
     /// This can be tuned depending on infrastructure as SSD/HDD will deal with resource contention very differently.
@@ -68,6 +92,99 @@ struct Opt {
     /// If this service should be read-only
     read_only: Option<bool>,
 
+    #[clap(long)]
+    /// Allow clients to preserve the timestamp of an imported object by supplying it as
+    /// RFC 3339 in the `x-amz-meta-s3ite-last-modified` header.
+    import_mode: Option<bool>,
+
+    #[clap(long)]
+    /// Return success instead of `BucketAlreadyExists` when creating a bucket that already exists.
+    idempotent_create_bucket: Option<bool>,
+
+    #[clap(long)]
+    /// The maximum number of buckets. `create_bucket` fails with `TooManyBuckets` once it is reached.
+    max_buckets: Option<usize>,
+
+    #[clap(long)]
+    /// Delete a bucket and all of its objects instead of returning `BucketNotEmpty` when it still contains objects.
+    force_delete_bucket: Option<bool>,
+
+    #[clap(long)]
+    /// Strip a leading slash and collapse repeated slashes in object keys so that `/foo//bar` and `foo/bar` are the same object.
+    normalize_keys: Option<bool>,
+
+    #[clap(long)]
+    /// Reject a `put_object` to a key which already exists instead of overwriting the object.
+    no_overwrite: Option<bool>,
+
+    #[clap(long)]
+    /// Serve the index document set by `put_bucket_website` for a `get_object` of a key ending in `/`
+    /// and the error document for a `get_object` of a missing key.
+    website: Option<bool>,
+
+    #[clap(long)]
+    /// Recompute the MD5 of an object read from a bucket and reject the read if it does not match the stored `ETag`.
+    verify_on_read: Option<bool>,
+
+    #[clap(long)]
+    /// Open the connections to each bucket in SQLite shared-cache mode so that they share one page cache.
+    /// Saves memory at the cost of write concurrency as lock conflicts return `SlowDown`.
+    shared_cache: Option<bool>,
+
+    #[clap(long)]
+    /// Checkpoint the WAL of a bucket into its database file when the last connection to it closes.
+    /// Disable for a faster shutdown at the cost of replaying the WAL when the bucket is next opened.
+    checkpoint_on_close: Option<bool>,
+
+    #[clap(long)]
+    /// Report a content type of `application/x-directory` for a `head_object` of a directory marker.
+    directory_content_type: Option<bool>,
+
+    #[clap(long)]
+    /// Record the time each object is first written, which is kept when it is overwritten, and return it from `head_object`.
+    track_created_at: Option<bool>,
+
+    #[clap(long)]
+    /// Reject a configuration with a combination of SQLite pragmas that risks corrupting a bucket instead of logging a warning.
+    strict_pragmas: Option<bool>,
+
+    #[clap(long)]
+    /// Create the tables in a `.sqlite3` file found in the root without them instead of refusing to start.
+    adopt_bare_buckets: Option<bool>,
+
+    #[clap(long)]
+    /// Log a warning when the WAL of a bucket exceeds this size in bytes at a maintenance checkpoint. Set to `0` to disable.
+    wal_warning_size: Option<u64>,
+
+    #[clap(long)]
+    /// Run a full `VACUUM` of a bucket once more than this percentage of its pages are free.
+    vacuum_freelist_percent: Option<u8>,
+
+    #[clap(long)]
+    /// Log a warning when a request has to wait behind this many other requests for a connection to a bucket. Set to `0` to disable.
+    pool_warning_waiting: Option<usize>,
+
+    #[clap(long)]
+    /// Close the connections to a bucket which has not been accessed for this many seconds. They are reopened on the next request.
+    idle_timeout: Option<u64>,
+
+    #[clap(long)]
+    /// Objects larger than this size in bytes are stored as files in a `<bucket>.blobs` directory next to the
+    /// bucket database instead of inside it.
+    blob_threshold: Option<u64>,
+
+    #[clap(long)]
+    /// The maximum number of recently read objects to hold in memory. Set to `0` to disable the object cache.
+    object_cache_capacity: Option<usize>,
+
+    #[clap(long)]
+    /// The maximum total size in bytes of all objects held in the object cache.
+    object_cache_size: Option<u64>,
+
+    #[clap(long)]
+    /// Only objects up to this size in bytes will be held in the object cache.
+    object_cache_object_size: Option<u64>,
+
     #[clap(long)]
     /// Controls the SQLite `journal_mode` flag pragma.
     journal_mode: Option<JournalMode>,
@@ -83,6 +200,27 @@ struct Opt {
     #[clap(long)]
     /// Controls the SQLite `cache_size` pragma in kilobytes.
     cache_size: Option<u32>,
+
+    #[clap(long)]
+    /// Controls the SQLite `busy_timeout` pragma in milliseconds.
+    busy_timeout: Option<u32>,
+
+    #[clap(long)]
+    /// The number of times a write is retried when the bucket database is busy or locked by
+    /// another connection before a `SlowDown` error is returned.
+    busy_retries: Option<u32>,
+
+    #[clap(long)]
+    /// The highest part number of a multipart upload and the most parts it can be completed with.
+    max_parts: Option<u32>,
+
+    #[clap(long)]
+    /// The largest object in bytes accepted by `put_object` or assembled by `complete_multipart_upload`.
+    max_object_size: Option<u64>,
+
+    #[clap(long)]
+    /// Reject an `upload_part` without a `Content-MD5` header so that every part is verified.
+    require_part_md5: Option<bool>,
 }
 
 #[tokio::main]
@@ -111,24 +249,99 @@ async fn main() -> Result {
     if let Some(port) = opt.port {
         config.port = port;
     }
+    if let Some(region) = opt.region {
+        config.region = region;
+    }
     if let Some(access_key) = opt.access_key {
         config.access_key = Some(access_key);
     }
     if let Some(secret_key) = opt.secret_key {
         config.secret_key = Some(secret_key);
     }
+    if let Some(owner_id) = opt.owner_id {
+        config.owner_id = Some(owner_id);
+    }
     if let Some(permissive_cors) = opt.permissive_cors {
         config.permissive_cors = permissive_cors;
     }
     if let Some(domain_name) = opt.domain_name {
         config.domain_name = Some(domain_name);
     }
+    if let Some(ready_file) = opt.ready_file {
+        config.ready_file = Some(ready_file);
+    }
     if let Some(concurrency_limit) = opt.concurrency_limit {
         config.concurrency_limit = concurrency_limit;
     }
     if let Some(read_only) = opt.read_only {
         config.read_only = read_only;
     }
+    if let Some(import_mode) = opt.import_mode {
+        config.import_mode = import_mode;
+    }
+    if let Some(idempotent_create_bucket) = opt.idempotent_create_bucket {
+        config.idempotent_create_bucket = idempotent_create_bucket;
+    }
+    if let Some(max_buckets) = opt.max_buckets {
+        config.max_buckets = Some(max_buckets);
+    }
+    if let Some(force_delete_bucket) = opt.force_delete_bucket {
+        config.force_delete_bucket = force_delete_bucket;
+    }
+    if let Some(normalize_keys) = opt.normalize_keys {
+        config.normalize_keys = normalize_keys;
+    }
+    if let Some(no_overwrite) = opt.no_overwrite {
+        config.no_overwrite = no_overwrite;
+    }
+    if let Some(website) = opt.website {
+        config.website = website;
+    }
+    if let Some(verify_on_read) = opt.verify_on_read {
+        config.verify_on_read = verify_on_read;
+    }
+    if let Some(shared_cache) = opt.shared_cache {
+        config.shared_cache = shared_cache;
+    }
+    if let Some(checkpoint_on_close) = opt.checkpoint_on_close {
+        config.checkpoint_on_close = checkpoint_on_close;
+    }
+    if let Some(directory_content_type) = opt.directory_content_type {
+        config.directory_content_type = directory_content_type;
+    }
+    if let Some(track_created_at) = opt.track_created_at {
+        config.track_created_at = track_created_at;
+    }
+    if let Some(strict_pragmas) = opt.strict_pragmas {
+        config.strict_pragmas = strict_pragmas;
+    }
+    if let Some(adopt_bare_buckets) = opt.adopt_bare_buckets {
+        config.adopt_bare_buckets = adopt_bare_buckets;
+    }
+    if let Some(wal_warning_size) = opt.wal_warning_size {
+        config.wal_warning_size = wal_warning_size;
+    }
+    if let Some(vacuum_freelist_percent) = opt.vacuum_freelist_percent {
+        config.vacuum_freelist_percent = Some(vacuum_freelist_percent);
+    }
+    if let Some(pool_warning_waiting) = opt.pool_warning_waiting {
+        config.pool_warning_waiting = pool_warning_waiting;
+    }
+    if let Some(idle_timeout) = opt.idle_timeout {
+        config.idle_timeout = Some(idle_timeout);
+    }
+    if let Some(blob_threshold) = opt.blob_threshold {
+        config.blob_threshold = Some(blob_threshold);
+    }
+    if let Some(object_cache_capacity) = opt.object_cache_capacity {
+        config.object_cache_capacity = object_cache_capacity;
+    }
+    if let Some(object_cache_size) = opt.object_cache_size {
+        config.object_cache_size = object_cache_size;
+    }
+    if let Some(object_cache_object_size) = opt.object_cache_object_size {
+        config.object_cache_object_size = object_cache_object_size;
+    }
     if let Some(journal_mode) = opt.journal_mode {
         config.sqlite.journal_mode = journal_mode;
     }
@@ -141,6 +354,32 @@ async fn main() -> Result {
     if let Some(cache_size) = opt.cache_size {
         config.sqlite.cache_size = cache_size;
     }
+    if let Some(busy_timeout) = opt.busy_timeout {
+        config.sqlite.busy_timeout = busy_timeout;
+    }
+    if let Some(busy_retries) = opt.busy_retries {
+        config.busy_retries = busy_retries;
+    }
+    if let Some(max_parts) = opt.max_parts {
+        config.max_parts = max_parts;
+    }
+    if let Some(max_object_size) = opt.max_object_size {
+        config.max_object_size = Some(max_object_size);
+    }
+    if let Some(require_part_md5) = opt.require_part_md5 {
+        config.require_part_md5 = require_part_md5;
+    }
+
+    if let Some(buckets_dir) = opt.buckets_dir {
+        config.buckets_dir = Some(buckets_dir);
+    }
+    config.merge_buckets_dir()?;
+
+    if opt.check_config {
+        config.validate()?;
+        info!("configuration is valid");
+        return Ok(());
+    }
 
     // Parse addr
     let addr = SocketAddr::new(config.host, config.port);
@@ -149,6 +388,11 @@ async fn main() -> Result {
     // Setup S3 provider
     let sqlite = Sqlite::new(&config).await?;
 
+    // the listener is bound and every bucket is open so connections will now be served
+    notify_ready(config.ready_file.as_deref())?;
+
+    let response_headers = config.response_header_map();
+
     // Setup S3 service
     let s3_service = {
         let mut s3 = S3ServiceBuilder::new(sqlite);
@@ -166,11 +410,24 @@ async fn main() -> Result {
         s3.build().into_shared()
     };
 
+    // Add the configured response headers to every response, including CORS preflights, unless
+    // the response already sets them
+    let response_headers_layer = MapResponseLayer::new(move |mut response: hyper::Response<_>| {
+        for (name, value) in &response_headers {
+            if response.headers().contains_key(name).not() {
+                response.headers_mut().insert(name, value.clone());
+            }
+        }
+        response
+    });
+
     // Run server
     // Add CorsLayer if defined
     if config.permissive_cors {
         let service = Shared::new(
             ServiceBuilder::new()
+                .layer(response_headers_layer)
+                .layer(MapResponseLayer::new(Sqlite::map_website_response))
                 .layer(CorsLayer::very_permissive())
                 .layer(ConcurrencyLimitLayer::new(config.concurrency_limit.into()))
                 .service(s3_service),
@@ -181,6 +438,8 @@ async fn main() -> Result {
     } else {
         let service = Shared::new(
             ServiceBuilder::new()
+                .layer(response_headers_layer)
+                .layer(MapResponseLayer::new(Sqlite::map_website_response))
                 .layer(ConcurrencyLimitLayer::new(config.concurrency_limit.into()))
                 .service(s3_service),
         );
@@ -193,6 +452,37 @@ async fn main() -> Result {
     Ok(())
 }
 
+/// Signal readiness to systemd if `NOTIFY_SOCKET` is set and write the `ready_file` if configured.
+/// Neither is done otherwise.
+fn notify_ready(ready_file: Option<&Path>) -> Result {
+    if let Some(ready_file) = ready_file {
+        fs::write(ready_file, format!("{}\n", std::process::id()))?;
+    }
+
+    #[cfg(unix)]
+    if let Some(notify_socket) = env::var_os("NOTIFY_SOCKET") {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::UnixDatagram;
+
+        let socket = UnixDatagram::unbound()?;
+
+        // a leading `@` names a socket in the linux abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = notify_socket.as_bytes().strip_prefix(b"@") {
+            use std::os::linux::net::SocketAddrExt;
+            use std::os::unix::net::SocketAddr as UnixSocketAddr;
+
+            let address = UnixSocketAddr::from_abstract_name(name)?;
+            socket.send_to_addr(b"READY=1", &address)?;
+            return Ok(());
+        }
+
+        socket.send_to(b"READY=1", &notify_socket)?;
+    }
+
+    Ok(())
+}
+
 async fn shutdown_signal() {
     let _ = tokio::signal::ctrl_c().await;
 }
@@ -1,13 +1,27 @@
+use crate::cache::CachedObject;
 use crate::error::*;
+use crate::sqlite::BucketPool;
 use crate::sqlite::ContinuationToken;
+use crate::sqlite::EncryptionRule;
+use crate::sqlite::KeyMetadata;
 use crate::sqlite::KeyValue;
+use crate::sqlite::LifecycleExpirationRule;
+use crate::sqlite::ListEntry;
 use crate::sqlite::Multipart;
+use crate::sqlite::MultipartUpload;
 use crate::sqlite::Sqlite;
+use crate::sqlite::Website;
+use crate::sqlite::ENCRYPTION_BUCKET_CONFIG;
+use crate::sqlite::ERROR_DOCUMENT_HEADER;
+use crate::sqlite::EXPIRES_AT_METADATA_KEY;
+use crate::sqlite::WEBSITE_BUCKET_CONFIG;
 use crate::utils::*;
 
 use bytes::Bytes;
 use futures::stream;
 use futures::TryStreamExt;
+use http::header::HeaderValue;
+use http::StatusCode;
 use md5::{Digest, Md5};
 use s3s::dto::*;
 use s3s::s3_error;
@@ -17,12 +31,32 @@ use s3s::S3ErrorCode::InternalError;
 use s3s::S3Result;
 use s3s::S3;
 use s3s::{S3Request, S3Response};
+use std::collections::{HashMap, HashSet};
 use std::ops::Not;
-use time::OffsetDateTime;
-use tokio::fs;
-use tracing::debug;
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
+/// The user metadata key used to supply the original timestamp of an object in import mode.
+const LAST_MODIFIED_METADATA_KEY: &str = "s3ite-last-modified";
+
+/// The user metadata key used to return the time an object was first written if `track_created_at` is set.
+const CREATED_AT_METADATA_KEY: &str = "s3ite-created-at";
+
+/// The response header of `head_bucket` holding the region of the bucket.
+const BUCKET_REGION_HEADER: &str = "x-amz-bucket-region";
+
+/// The MD5 of an empty body reported as the ETag of a directory marker.
+const EMPTY_MD5: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+/// The content type reported for a directory marker if `directory_content_type` is set.
+const DIRECTORY_CONTENT_TYPE: &str = "application/x-directory";
+
+/// How long `delete_bucket` waits for the in-flight requests of the bucket before giving up.
+const DELETE_BUCKET_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 #[async_trait::async_trait]
 impl S3 for Sqlite {
     #[tracing::instrument]
@@ -30,13 +64,51 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<CreateBucketInput>,
     ) -> S3Result<S3Response<CreateBucketOutput>> {
-        let CreateBucketInput { bucket, .. } = req.input;
+        self.validate_operation(req.credentials.as_ref(), "CreateBucket")?;
+
+        let CreateBucketInput {
+            bucket,
+            create_bucket_configuration,
+            ..
+        } = req.input;
 
         self.validate_mutable_bucket(&bucket)?;
 
-        if self.buckets.read().await.contains_key(&bucket) {
+        if let Some(location_constraint) = create_bucket_configuration
+            .and_then(|create_bucket_configuration| create_bucket_configuration.location_constraint)
+        {
+            if location_constraint.as_str() != self.config.region {
+                let mut err = S3Error::with_message(
+                    S3ErrorCode::Custom("IllegalLocationConstraintException".into()),
+                    format!(
+                        "The {} location constraint is incompatible for the region specific endpoint this request was sent to.",
+                        location_constraint.as_str()
+                    ),
+                );
+                err.set_status_code(StatusCode::BAD_REQUEST);
+                return Err(err);
+            }
+        }
+
+        let buckets = self.buckets.read().await;
+        if buckets.contains_key(&bucket) {
+            if self.config.idempotent_create_bucket {
+                let output = CreateBucketOutput {
+                    location: Some(format!("/{bucket}")),
+                };
+                return Ok(S3Response::new(output));
+            }
             return Err(s3_error!(BucketAlreadyExists));
         }
+        if let Some(max_buckets) = self.config.max_buckets {
+            if buckets.len() >= max_buckets {
+                return Err(s3_error!(
+                    TooManyBuckets,
+                    "You have attempted to create more buckets than allowed."
+                ));
+            }
+        }
+        drop(buckets);
 
         let file_path = self.get_bucket_path(&bucket)?;
         if file_path.exists() {
@@ -45,7 +117,7 @@ impl S3 for Sqlite {
 
         self.try_create_bucket(&bucket, file_path)
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+            .map_err(to_s3_error)?;
 
         let output = CreateBucketOutput::default(); // TODO: handle other fields
         Ok(S3Response::new(output))
@@ -56,42 +128,92 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<CopyObjectInput>,
     ) -> S3Result<S3Response<CopyObjectOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "CopyObject")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        // `If-None-Match: *` refuses to overwrite an existing target
+        let if_none_match = req.headers.get(http::header::IF_NONE_MATCH);
+        if if_none_match.is_some_and(|if_none_match| if_none_match != "*") {
+            return Err(s3_error!(
+                NotImplemented,
+                "only an If-None-Match of * is supported for a copy"
+            ));
+        }
+        let if_none_match = if_none_match.is_some();
+
         let CopyObjectInput {
             bucket: tgt_bucket,
             key: tgt_key,
             copy_source,
+            metadata_directive,
+            metadata,
+            content_type,
             ..
         } = req.input;
+        let tgt_key = self.normalize_key(tgt_key);
 
         self.validate_mutable_bucket(&tgt_bucket)?;
 
         let (src_bucket, src_key) = match copy_source {
             CopySource::AccessPoint { .. } => return Err(s3_error!(NotImplemented)),
-            CopySource::Bucket { bucket, key, .. } => (bucket, key),
+            CopySource::Bucket { bucket, key, .. } => (bucket, self.normalize_key(key.into())),
+        };
+
+        let replace_metadata = metadata_directive
+            .as_ref()
+            .is_some_and(|metadata_directive| {
+                metadata_directive.as_str() == MetadataDirective::REPLACE
+            });
+
+        // copying an object onto itself is only allowed if it changes the metadata
+        if &*src_bucket == tgt_bucket.as_str() && src_key == tgt_key && replace_metadata.not() {
+            return Err(s3_error!(
+                InvalidRequest,
+                "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata, storage class, website redirect location or encryption attributes."
+            ));
+        }
+
+        // the source bucket is read without a permit of its `concurrency_limit` but holds its gate
+        // so that it cannot be deleted during the copy
+        let _src_gate = if &*src_bucket == tgt_bucket.as_str() {
+            None
+        } else {
+            self.acquire_bucket_gate(&src_bucket).await
         };
 
         // verify source and target buckets exist
         let bucket_pool = self.try_get_bucket_pool(&src_bucket).await?;
 
+        let src_blob_dir = self.get_blob_dir(&src_bucket)?;
         let mut object = bucket_pool
             .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
-                Self::try_get_object(&transaction, &src_key)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-                    .ok_or_else(|| s3_error!(NoSuchKey))
+                let transaction = connection.transaction().map_err(to_s3_error)?;
+                let mut object = Self::try_get_object(&transaction, &src_key)
+                    .map_err(to_s3_error)?
+                    .ok_or_else(|| s3_error!(NoSuchKey))?;
+                Self::try_load_value(&src_blob_dir, &mut object).map_err(to_s3_error)?;
+                Ok(object)
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+            .map_err(to_s3_error)?
             .map_err(|err| match err.code() {
-                S3ErrorCode::NoSuchKey => err,
+                S3ErrorCode::NoSuchKey
+                | S3ErrorCode::SlowDown
+                | S3ErrorCode::ServiceUnavailable => err,
                 _ => s3_error!(InternalError),
             })?;
 
         // replace key with target key
         object.key = tgt_key.to_string();
 
+        // the `REPLACE` directive takes the metadata and content type from the request only so any
+        // header omitted from the request is reset rather than copied from the source. a request
+        // without any `x-amz-meta-*` headers strips the user metadata from the object.
+        if replace_metadata {
+            object.metadata = metadata.filter(|metadata| metadata.is_empty().not());
+            object.content_type = content_type.map(|content_type| content_type.to_string());
+        }
+
         let copy_object_result = CopyObjectResult {
             e_tag: object.md5.clone(),
             last_modified: Some(object.last_modified.into()),
@@ -99,15 +221,29 @@ impl S3 for Sqlite {
         };
 
         let bucket_pool = self.try_get_bucket_pool(&tgt_bucket).await?;
+        let tgt_blob_dir = self.get_blob_dir(&tgt_bucket)?;
+        let blob_threshold = self.config.blob_threshold(Some(&tgt_bucket));
+        let busy_retries = self.config.busy_retries;
         bucket_pool
             .interact(move |connection| {
-                let transaction = connection.transaction()?;
-                Self::try_put_object(&transaction, object)?;
-                transaction.commit()
+                let transaction = Self::try_write_transaction(connection, busy_retries)?;
+                // checked in the write transaction so that a concurrent write cannot be overwritten
+                if if_none_match && Self::try_get_metadata(&transaction, &object.key)?.is_some() {
+                    Err(s3_error!(PreconditionFailed))?;
+                }
+                let replaced_blob =
+                    Self::try_put_object_blob(&transaction, &tgt_blob_dir, blob_threshold, object)?;
+                transaction.commit()?;
+                Self::retire_replaced_blob(&tgt_blob_dir, replaced_blob);
+                Ok::<_, Error>(())
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+            .map_err(to_s3_error)??;
+
+        self.object_cache
+            .lock()
+            .unwrap()
+            .invalidate(&tgt_bucket, &tgt_key);
 
         let output = CopyObjectOutput {
             copy_object_result: Some(copy_object_result),
@@ -121,69 +257,228 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<DeleteBucketInput>,
     ) -> S3Result<S3Response<DeleteBucketOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteBucket")?;
+
         let DeleteBucketInput { bucket, .. } = req.input;
 
         self.validate_mutable_bucket(&bucket)?;
 
-        let mut guard = self.buckets.write().await;
-        match guard.get(&bucket) {
-            Some(connection) => {
-                connection.close();
-                let bucket_path = self.get_bucket_path(&bucket)?;
-                fs::remove_file(&bucket_path)
-                    .await
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
-                fs::remove_file(format!("{}-wal", bucket_path.to_string_lossy()))
-                    .await
-                    .ok();
-                fs::remove_file(format!("{}-shm", bucket_path.to_string_lossy()))
-                    .await
-                    .ok();
-                guard.remove(&bucket);
-            }
-            None => return Err(s3_error!(NoSuchBucket)),
+        let bucket_gate = self
+            .buckets
+            .read()
+            .await
+            .get(&bucket)
+            .map(|bucket_pool| bucket_pool.gate.clone())
+            .ok_or_else(|| s3_error!(NoSuchBucket))?;
+
+        // wait for the in-flight operations on the bucket to release their permits. new operations
+        // wait at the gate until the bucket is either deleted or the deletion has failed.
+        let deadline = tokio::time::Instant::now() + DELETE_BUCKET_TIMEOUT;
+        let _bucket_gate = tokio::time::timeout_at(deadline, bucket_gate.write_owned())
+            .await
+            .map_err(|_| delete_bucket_timeout(&bucket))?;
+
+        // every connection is checked out while holding the gate so none are in use once the
+        // write guard is held
+        let mut guard = tokio::time::timeout_at(deadline, self.buckets.write())
+            .await
+            .map_err(|_| delete_bucket_timeout(&bucket))?;
+        let Some(bucket_pool) = guard.get(&bucket) else {
+            return Err(s3_error!(NoSuchBucket));
         };
 
+        if self.config.force_delete_bucket.not() {
+            let is_empty = bucket_pool
+                .pool
+                .get()
+                .await
+                .map_err(to_s3_error)?
+                .interact(|connection| {
+                    let transaction = connection.transaction()?;
+                    Self::try_is_empty(&transaction)
+                })
+                .await
+                .map_err(to_s3_error)?
+                .map_err(to_s3_error)?;
+
+            if is_empty.not() {
+                return Err(s3_error!(BucketNotEmpty));
+            }
+        }
+
+        bucket_pool.pool.close();
+        let creation_date = bucket_pool.creation_date;
+        let semaphore = bucket_pool.semaphore.clone();
+        let gate = bucket_pool.gate.clone();
+        let last_access = bucket_pool.last_access.clone();
+        let bucket_path = self.get_bucket_path(&bucket)?;
+
+        // only forget the bucket once all of its files are removed. otherwise reopen it so that it
+        // remains listed and usable
+        let blob_dir = self.get_blob_dir(&bucket)?;
+        if let Err(err) = Self::try_remove_bucket_files(&bucket_path, &blob_dir).await {
+            let pool = self.try_open_bucket_pool(&bucket, bucket_path).await?;
+            *last_access.lock().unwrap() = Some(std::time::Instant::now());
+            guard.insert(
+                bucket,
+                BucketPool {
+                    pool,
+                    creation_date,
+                    wal_stats: None,
+                    semaphore,
+                    gate,
+                    last_access,
+                    server_side_encryption: Arc::default(),
+                },
+            );
+            return Err(to_s3_error(err));
+        }
+
+        guard.remove(&bucket);
+        self.object_cache.lock().unwrap().invalidate_bucket(&bucket);
+
         Ok(S3Response::new(DeleteBucketOutput {}))
     }
 
+    #[tracing::instrument]
+    async fn delete_bucket_encryption(
+        &self,
+        req: S3Request<DeleteBucketEncryptionInput>,
+    ) -> S3Result<S3Response<DeleteBucketEncryptionOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteBucketEncryption")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let DeleteBucketEncryptionInput { bucket, .. } = req.input;
+
+        self.validate_mutable_bucket(&bucket)?;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_bucket_config::<Vec<EncryptionRule>>(
+                    &transaction,
+                    ENCRYPTION_BUCKET_CONFIG,
+                    None,
+                )?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+        self.forget_server_side_encryption(&bucket).await;
+
+        Ok(S3Response::new(DeleteBucketEncryptionOutput::default()))
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket_lifecycle(
+        &self,
+        req: S3Request<DeleteBucketLifecycleInput>,
+    ) -> S3Result<S3Response<DeleteBucketLifecycleOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteBucketLifecycle")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let DeleteBucketLifecycleInput { bucket, .. } = req.input;
+
+        self.validate_mutable_bucket(&bucket)?;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_lifecycle_rules(&transaction, &[])?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        Ok(S3Response::new(DeleteBucketLifecycleOutput::default()))
+    }
+
+    #[tracing::instrument]
+    async fn delete_bucket_website(
+        &self,
+        req: S3Request<DeleteBucketWebsiteInput>,
+    ) -> S3Result<S3Response<DeleteBucketWebsiteOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteBucketWebsite")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let DeleteBucketWebsiteInput { bucket, .. } = req.input;
+
+        self.validate_mutable_bucket(&bucket)?;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_bucket_config::<Website>(&transaction, WEBSITE_BUCKET_CONFIG, None)?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        Ok(S3Response::new(DeleteBucketWebsiteOutput::default()))
+    }
+
     #[tracing::instrument]
     async fn delete_object(
         &self,
         req: S3Request<DeleteObjectInput>,
     ) -> S3Result<S3Response<DeleteObjectOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteObject")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let DeleteObjectInput { bucket, key, .. } = req.input;
+        let key = self.normalize_key(key);
+
+        self.validate_mutable_bucket(&bucket)?;
+
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
 
+        let key_clone = key.clone();
+        let blob_dir = self.get_blob_dir(&bucket)?;
         bucket_pool
             .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let transaction = connection.transaction().map_err(to_s3_error)?;
 
                 // if is directory
-                if key.ends_with('/') {
-                    let rows_affected = Self::try_delete_objects_like(&transaction, &key)
-                        .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let deleted_blob = if key_clone.ends_with('/') {
+                    let rows_affected = Self::try_delete_objects_like(&transaction, &key_clone)
+                        .map_err(to_s3_error)?;
 
                     if rows_affected > 1 {
                         return Err(s3_error!(BucketNotEmpty));
                     }
+                    None
                 } else {
-                    let rows_affected = Self::try_delete_object(&transaction, &key)
-                        .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                    let blob = Self::try_get_blob(&transaction, &key_clone).map_err(to_s3_error)?;
+                    let rows_affected =
+                        Self::try_delete_object(&transaction, &key_clone).map_err(to_s3_error)?;
 
                     if rows_affected != 1 {
                         return Err(s3_error!(NoSuchKey));
                     }
-                }
+                    blob
+                };
 
-                transaction
-                    .commit()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))
+                transaction.commit().map_err(to_s3_error)?;
+                Self::retire_replaced_blob(&blob_dir, deleted_blob);
+                Ok(())
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))??;
+            .map_err(to_s3_error)??;
+
+        let mut object_cache = self.object_cache.lock().unwrap();
+        if key.ends_with('/') {
+            object_cache.invalidate_prefix(&bucket, &key);
+        } else {
+            object_cache.invalidate(&bucket, &key);
+        }
+        drop(object_cache);
 
         let output = DeleteObjectOutput::default(); // TODO: handle other fields
         Ok(S3Response::new(output))
@@ -194,14 +489,23 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<DeleteObjectsInput>,
     ) -> S3Result<S3Response<DeleteObjectsOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "DeleteObjects")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let DeleteObjectsInput { bucket, delete, .. } = req.input;
 
         self.validate_mutable_bucket(&bucket)?;
 
+        // S3 rejects a delete request which does not list any objects
+        if delete.objects.is_empty() {
+            return Err(s3_error!(MalformedXML));
+        }
+
         let delete_keys = delete
             .objects
             .into_iter()
-            .map(|object| object.key)
+            .map(|object| self.normalize_key(object.key))
             .collect::<Vec<_>>();
 
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
@@ -214,8 +518,14 @@ impl S3 for Sqlite {
                 rusqlite::Result::<_, rusqlite::Error>::Ok(affected_keys)
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        let mut object_cache = self.object_cache.lock().unwrap();
+        for key in &affected_keys {
+            object_cache.invalidate(&bucket, key);
+        }
+        drop(object_cache);
 
         let output = DeleteObjectsOutput {
             deleted: Some(
@@ -232,11 +542,116 @@ impl S3 for Sqlite {
         Ok(S3Response::new(output))
     }
 
+    #[tracing::instrument]
+    async fn get_bucket_encryption(
+        &self,
+        req: S3Request<GetBucketEncryptionInput>,
+    ) -> S3Result<S3Response<GetBucketEncryptionOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "GetBucketEncryption")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let GetBucketEncryptionInput { bucket, .. } = req.input;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        let rules = bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_get_bucket_config::<Vec<EncryptionRule>>(
+                    &transaction,
+                    ENCRYPTION_BUCKET_CONFIG,
+                )
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        let Some(rules) = rules else {
+            let mut err = S3Error::with_message(
+                S3ErrorCode::Custom("ServerSideEncryptionConfigurationNotFoundError".into()),
+                "The server side encryption configuration was not found",
+            );
+            err.set_status_code(StatusCode::NOT_FOUND);
+            return Err(err);
+        };
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| ServerSideEncryptionRule {
+                apply_server_side_encryption_by_default: rule.sse_algorithm.map(|sse_algorithm| {
+                    ServerSideEncryptionByDefault {
+                        kms_master_key_id: rule.kms_master_key_id,
+                        sse_algorithm: ServerSideEncryption::from(sse_algorithm),
+                    }
+                }),
+                bucket_key_enabled: rule.bucket_key_enabled,
+            })
+            .collect();
+
+        let output = GetBucketEncryptionOutput {
+            server_side_encryption_configuration: Some(ServerSideEncryptionConfiguration { rules }),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_lifecycle_configuration(
+        &self,
+        req: S3Request<GetBucketLifecycleConfigurationInput>,
+    ) -> S3Result<S3Response<GetBucketLifecycleConfigurationOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "GetBucketLifecycleConfiguration")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let GetBucketLifecycleConfigurationInput { bucket, .. } = req.input;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        let rules = bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_get_lifecycle_rules(&transaction)
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        if rules.is_empty() {
+            return Err(s3_error!(NoSuchLifecycleConfiguration));
+        }
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| LifecycleRule {
+                abort_incomplete_multipart_upload: None,
+                expiration: Some(LifecycleExpiration {
+                    date: None,
+                    days: rule.expiration_days,
+                    expired_object_delete_marker: false,
+                }),
+                filter: Some(LifecycleRuleFilter::Prefix(rule.prefix)),
+                id: rule.id,
+                noncurrent_version_expiration: None,
+                noncurrent_version_transitions: None,
+                prefix: None,
+                status: ExpirationStatus::from_static(if rule.enabled {
+                    ExpirationStatus::ENABLED
+                } else {
+                    ExpirationStatus::DISABLED
+                }),
+                transitions: None,
+            })
+            .collect();
+
+        let output = GetBucketLifecycleConfigurationOutput { rules: Some(rules) };
+        Ok(S3Response::new(output))
+    }
+
     #[tracing::instrument]
     async fn get_bucket_location(
         &self,
         req: S3Request<GetBucketLocationInput>,
     ) -> S3Result<S3Response<GetBucketLocationOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "GetBucketLocation")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
         let GetBucketLocationInput { bucket, .. } = req.input;
 
         if self.buckets.read().await.contains_key(&bucket).not() {
@@ -248,7 +663,43 @@ impl S3 for Sqlite {
             return Err(s3_error!(NoSuchBucket));
         }
 
-        let output = GetBucketLocationOutput::default();
+        // buckets in `us-east-1` are reported without a location constraint
+        let output = GetBucketLocationOutput {
+            location_constraint: (self.config.region != "us-east-1")
+                .then(|| BucketLocationConstraint::from(self.config.region.clone())),
+        };
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument]
+    async fn get_bucket_website(
+        &self,
+        req: S3Request<GetBucketWebsiteInput>,
+    ) -> S3Result<S3Response<GetBucketWebsiteOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "GetBucketWebsite")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let GetBucketWebsiteInput { bucket, .. } = req.input;
+
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        let website = bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_get_bucket_config::<Website>(&transaction, WEBSITE_BUCKET_CONFIG)
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?
+            .ok_or_else(|| s3_error!(NoSuchWebsiteConfiguration))?;
+
+        let output = GetBucketWebsiteOutput {
+            error_document: website.error_document.map(|key| ErrorDocument { key }),
+            index_document: website
+                .index_document
+                .map(|suffix| IndexDocument { suffix }),
+            redirect_all_requests_to: None,
+            routing_rules: None,
+        };
         Ok(S3Response::new(output))
     }
 
@@ -257,56 +708,232 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<GetObjectInput>,
     ) -> S3Result<S3Response<GetObjectOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "GetObject")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let GetObjectInput {
-            bucket, key, range, ..
+            bucket,
+            key,
+            range,
+            if_none_match,
+            if_modified_since,
+            response_cache_control,
+            response_content_disposition,
+            response_content_encoding,
+            response_content_language,
+            response_content_type,
+            response_expires,
+            ..
         } = req.input;
+        let if_modified_since = if_modified_since.map(OffsetDateTime::from);
+        let key = self.normalize_key(key);
+        let key = self.try_resolve_index_document(&bucket, key).await?;
 
-        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        let (cached, generation) = {
+            let mut object_cache = self.object_cache.lock().unwrap();
+            (object_cache.get(&bucket, &key), object_cache.generation())
+        };
 
-        let object = bucket_pool
-            .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
-                Self::try_get_object(&transaction, &key)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-                    .ok_or_else(|| s3_error!(NoSuchKey))
-            })
-            .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))??;
+        let fetched = async {
+            Ok::<_, S3Error>(match cached {
+                Some(object) => {
+                    check_if_none_match(
+                        if_none_match.as_deref(),
+                        object.md5.as_deref(),
+                        object.last_modified,
+                    )?;
+                    check_if_modified_since(
+                        if_none_match.as_deref(),
+                        if_modified_since,
+                        object.md5.as_deref(),
+                        object.last_modified,
+                    )?;
+                    let object_range = object_range(range, object.size)?;
+                    let value = object.value.slice(
+                        try_!(usize::try_from(object_range.start))
+                            ..try_!(usize::try_from(object_range.end)),
+                    );
+                    let object = KeyMetadata {
+                        size: object.size,
+                        metadata: object.metadata,
+                        content_type: object.content_type,
+                        storage_class: object.storage_class,
+                        last_modified: object.last_modified,
+                        md5: object.md5,
+                    };
+                    (object, object_range, value)
+                }
+                None => {
+                    let key_clone = key.clone();
+                    let blob_dir = self.get_blob_dir(&bucket)?;
+                    let verify_on_read = self.config.verify_on_read;
+                    let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+                    let (object, object_range, value) = bucket_pool
+                        .interact(move |connection| {
+                            let transaction = connection
+                                .transaction()
+                                .map_err(to_s3_error)?;
+
+                            // evaluate the conditions and range against the metadata so that the
+                            // value is only read when it will be returned and only the requested bytes
+                            let object = Self::try_get_metadata(&transaction, &key_clone)
+                                .map_err(to_s3_error)?
+                                .ok_or_else(|| s3_error!(NoSuchKey))?;
+                            check_if_none_match(
+                                if_none_match.as_deref(),
+                                object.md5.as_deref(),
+                                object.last_modified,
+                            )?;
+                            check_if_modified_since(
+                                if_none_match.as_deref(),
+                                if_modified_since,
+                                object.md5.as_deref(),
+                                object.last_modified,
+                            )?;
+                            let object_range = object_range(range, object.size)?;
+
+                            // verifying the value requires reading all of it even for a range
+                            let read_range = match (verify_on_read, &object.md5) {
+                                (true, Some(_)) => 0..object.size,
+                                _ => object_range.clone(),
+                            };
+                            let value = Bytes::from(
+                                Self::try_get_value_range(
+                                    &transaction,
+                                    &blob_dir,
+                                    &key_clone,
+                                    &read_range,
+                                )
+                                .map_err(S3Error::from)?,
+                            );
+
+                            if let (true, Some(md5)) = (verify_on_read, &object.md5) {
+                                let mut md5_hash = Md5::new();
+                                md5_hash.update(&value);
+                                if hex(md5_hash.finalize()) != *md5 {
+                                    error!(key = %key_clone, %md5, "object failed verification on read");
+                                    return Err(s3_error!(
+                                        InternalError,
+                                        "the object {key_clone} does not match its stored checksum"
+                                    ));
+                                }
+                            }
+
+                            let value = value.slice(
+                                try_!(usize::try_from(object_range.start - read_range.start))
+                                    ..try_!(usize::try_from(object_range.end - read_range.start)),
+                            );
+
+                            Ok((object, object_range, value))
+                        })
+                        .await
+                        .map_err(to_s3_error)??;
+
+                    // only a complete value can be cached and an object which expires is not cached
+                    // so that it cannot be served from the cache after it has expired
+                    let expires = object
+                        .metadata
+                        .as_ref()
+                        .is_some_and(|metadata| metadata.contains_key(EXPIRES_AT_METADATA_KEY));
+                    if range.is_none() && expires.not() {
+                        self.object_cache.lock().unwrap().insert(
+                            &bucket,
+                            &key,
+                            CachedObject {
+                                value: value.clone(),
+                                size: object.size,
+                                metadata: object.metadata.clone(),
+                                content_type: object.content_type.clone(),
+                                storage_class: object.storage_class.clone(),
+                                last_modified: object.last_modified,
+                                md5: object.md5.clone(),
+                            },
+                            generation,
+                        );
+                    }
 
-        let content_length = match range {
-            None => object.size,
-            Some(range) => {
-                let object_range = range.check(object.size)?;
-                object_range.end - object_range.start
-            }
-        };
-        let content_length_i64 = try_!(i64::try_from(content_length));
+                    (object, object_range, value)
+                }
+            })
+        }
+        .await;
 
-        let value = match range {
-            Some(Range::Int { first, .. }) => {
-                let first = try_!(usize::try_from(first));
-                Bytes::copy_from_slice(&object.value.unwrap()[first..])
+        // a missing key of a website is answered with all of its error document, if there is one
+        let (object, object_range, value, range, error_document) = match fetched {
+            Err(err) if self.config.website && matches!(err.code(), S3ErrorCode::NoSuchKey) => {
+                let Some(error_document) = self.try_get_error_document(&bucket).await? else {
+                    return Err(err);
+                };
+                let value = Bytes::from(error_document.value.unwrap_or_default());
+                let object = KeyMetadata {
+                    size: error_document.size,
+                    metadata: error_document.metadata,
+                    content_type: error_document.content_type,
+                    storage_class: error_document.storage_class,
+                    last_modified: error_document.last_modified,
+                    md5: error_document.md5,
+                };
+                (object, 0..error_document.size, value, None, true)
             }
-            Some(Range::Suffix { length }) => {
-                let first = try_!(usize::try_from(object.size - length));
-                Bytes::copy_from_slice(&object.value.unwrap()[first..])
+            fetched => {
+                let (object, object_range, value) = fetched?;
+                (object, object_range, value, range, false)
             }
-            None => Bytes::copy_from_slice(&object.value.unwrap()),
         };
 
+        let content_length_i64 = try_!(i64::try_from(object_range.end - object_range.start));
+        let content_range = range.map(|_| {
+            format!(
+                "bytes {}-{}/{}",
+                object_range.start,
+                object_range.end.saturating_sub(1),
+                object.size
+            )
+        });
+
+        let server_side_encryption = self.try_get_server_side_encryption(&bucket).await?;
+
         let body = stream::once(async { Ok(value) });
 
+        // the `response-*` query parameters, typically carried by a presigned URL, override the
+        // headers of the response
+        let content_type = response_content_type
+            .as_deref()
+            .or(object.content_type.as_deref());
+
         let output = GetObjectOutput {
             body: Some(StreamingBlob::wrap::<_, S3Error>(body)),
+            accept_ranges: Some("bytes".into()),
             content_length: content_length_i64,
+            content_range,
+            content_type: Some(parse_content_type(content_type)),
+            cache_control: response_cache_control,
+            content_disposition: response_content_disposition.or_else(|| {
+                self.config
+                    .content_disposition(&bucket)
+                    .map(|disposition| content_disposition(disposition, &key))
+            }),
+            content_encoding: response_content_encoding,
+            content_language: response_content_language,
+            expires: response_expires,
             last_modified: Some(object.last_modified.into()),
             metadata: object.metadata,
             e_tag: object.md5,
+            server_side_encryption,
+            storage_class: object.storage_class.map(StorageClass::from),
             ..Default::default()
         };
-        Ok(S3Response::new(output))
+
+        // the status of a successful response cannot be set here so the error document is marked
+        // for `Sqlite::map_website_response` to answer with `404 Not Found`
+        let mut response = S3Response::new(output);
+        if error_document {
+            response
+                .headers
+                .insert(ERROR_DOCUMENT_HEADER, HeaderValue::from_static("true"));
+        }
+        Ok(response)
     }
 
     #[tracing::instrument]
@@ -314,6 +941,9 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<HeadBucketInput>,
     ) -> S3Result<S3Response<HeadBucketOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "HeadBucket")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
         let HeadBucketInput { bucket, .. } = req.input;
 
         if self.buckets.read().await.contains_key(&bucket).not() {
@@ -325,7 +955,13 @@ impl S3 for Sqlite {
             return Err(s3_error!(NoSuchBucket));
         }
 
-        Ok(S3Response::new(HeadBucketOutput {}))
+        // `HeadBucketOutput` has no `bucket_region` so the header is set directly
+        let mut response = S3Response::new(HeadBucketOutput {});
+        response.headers.insert(
+            BUCKET_REGION_HEADER,
+            try_!(HeaderValue::from_str(&self.config.region)),
+        );
+        Ok(response)
     }
 
     #[tracing::instrument]
@@ -333,34 +969,59 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<HeadObjectInput>,
     ) -> S3Result<S3Response<HeadObjectOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "HeadObject")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let HeadObjectInput { bucket, key, .. } = req.input;
+        let key = self.normalize_key(key);
 
-        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        let mut object = self.try_get_key_metadata(&bucket, &key).await?;
+        let disposition = self
+            .config
+            .content_disposition(&bucket)
+            .map(|disposition| content_disposition(disposition, &key));
 
-        let object = bucket_pool
-            .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
-                Self::try_get_metadata(&transaction, &key)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-                    .ok_or_else(|| s3_error!(NoSuchKey))
-            })
-            .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-            .map_err(|err| match err.code() {
-                S3ErrorCode::NoSuchKey => err,
-                _ => s3_error!(InternalError),
-            })?;
+        // a directory marker has no body so report the ETag of an empty object
+        if key.ends_with('/') {
+            object.md5 = object.md5.or_else(|| Some(EMPTY_MD5.to_string()));
+            if self.config.directory_content_type {
+                object.content_type = Some(DIRECTORY_CONTENT_TYPE.to_string());
+            }
+        }
+
+        if self.config.track_created_at {
+            let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+            let created_at = bucket_pool
+                .interact(move |connection| {
+                    let transaction = connection.transaction()?;
+                    Self::try_get_created_at(&transaction, &key)
+                })
+                .await
+                .map_err(to_s3_error)?
+                .map_err(to_s3_error)?;
+
+            if let Some(created_at) = created_at {
+                object.metadata.get_or_insert_with(HashMap::new).insert(
+                    CREATED_AT_METADATA_KEY.to_string(),
+                    try_!(created_at.format(&Rfc3339)),
+                );
+            }
+        }
 
-        // TODO: detect content type
-        let content_type = mime::APPLICATION_OCTET_STREAM;
+        let server_side_encryption = self.try_get_server_side_encryption(&bucket).await?;
 
+        // every object supports ranged reads and a `head_object` always reports the full length
         let output = HeadObjectOutput {
+            accept_ranges: Some("bytes".into()),
             content_length: try_!(i64::try_from(object.size)),
-            content_type: Some(content_type),
+            content_disposition: disposition,
+            content_type: Some(parse_content_type(object.content_type.as_deref())),
             last_modified: Some(object.last_modified.into()),
             metadata: object.metadata,
+            e_tag: object.md5,
+            server_side_encryption,
+            storage_class: object.storage_class.map(StorageClass::from),
             ..Default::default()
         };
         Ok(S3Response::new(output))
@@ -371,28 +1032,27 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<ListBucketsInput>,
     ) -> S3Result<S3Response<ListBucketsOutput>> {
-        let ListBucketsInput {} = req.input;
-
-        let mut buckets: Vec<Bucket> = Vec::new();
+        self.validate_operation(req.credentials.as_ref(), "ListBuckets")?;
 
-        for name in self.buckets.read().await.keys() {
-            let file_path = self.get_bucket_path(name)?;
-            let file_meta = fs::metadata(file_path)
-                .await
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
-            let created_or_modified_date =
-                Timestamp::from(try_!(file_meta.created().or(file_meta.modified())));
+        let ListBucketsInput {} = req.input;
 
-            let bucket = Bucket {
-                creation_date: Some(created_or_modified_date),
+        let buckets = self
+            .buckets
+            .read()
+            .await
+            .iter()
+            .map(|(name, bucket_pool)| Bucket {
+                creation_date: Some(bucket_pool.creation_date.into()),
                 name: Some(name.clone()),
-            };
-            buckets.push(bucket);
-        }
+            })
+            .collect::<Vec<_>>();
 
         let output = ListBucketsOutput {
             buckets: Some(buckets),
-            owner: None,
+            owner: self.config.owner_id.as_ref().map(|owner_id| Owner {
+                display_name: None,
+                id: Some(owner_id.clone()),
+            }),
         };
         Ok(S3Response::new(output))
     }
@@ -402,33 +1062,52 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<ListObjectsInput>,
     ) -> S3Result<S3Response<ListObjectsOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "ListObjects")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let marker = req.input.marker.clone();
 
-        let v2_resp = self.list_objects_v2(req.map_input(Into::into)).await?;
+        // the operation was validated as `ListObjects` so it is not validated again as `ListObjectsV2`
+        let access_key = req
+            .credentials
+            .as_ref()
+            .map(|credentials| credentials.access_key.clone());
+        let v2 = self
+            .try_list_objects_v2(access_key, req.input.into())
+            .await?;
+
+        // the marker continues after whichever of the last key or common prefix sorts last
+        let next_marker = v2
+            .is_truncated
+            .then(|| {
+                let last_key = v2.contents.as_ref().and_then(|contents| {
+                    contents.last().and_then(|last| last.key.as_ref().cloned())
+                });
+                let last_common_prefix = v2.common_prefixes.as_ref().and_then(|common_prefixes| {
+                    common_prefixes
+                        .last()
+                        .and_then(|last| last.prefix.as_ref().cloned())
+                });
+                last_key.max(last_common_prefix)
+            })
+            .flatten();
+
+        let output = ListObjectsOutput {
+            contents: v2.contents,
+            common_prefixes: v2.common_prefixes,
+            delimiter: v2.delimiter,
+            encoding_type: v2.encoding_type,
+            name: v2.name,
+            prefix: v2.prefix,
+            max_keys: v2.max_keys,
+            is_truncated: v2.is_truncated,
+            marker,
+            next_marker,
+            ..Default::default()
+        };
 
-        Ok(v2_resp.map_output(|v2| {
-            let next_marker = v2
-                .is_truncated
-                .then(|| {
-                    v2.contents.as_ref().and_then(|contents| {
-                        contents.last().and_then(|last| last.key.as_ref().cloned())
-                    })
-                })
-                .flatten();
-
-            ListObjectsOutput {
-                contents: v2.contents,
-                delimiter: v2.delimiter,
-                encoding_type: v2.encoding_type,
-                name: v2.name,
-                prefix: v2.prefix,
-                max_keys: v2.max_keys,
-                is_truncated: v2.is_truncated,
-                marker,
-                next_marker,
-                ..Default::default()
-            }
-        }))
+        Ok(S3Response::new(output))
     }
 
     #[tracing::instrument]
@@ -436,110 +1115,187 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<ListObjectsV2Input>,
     ) -> S3Result<S3Response<ListObjectsV2Output>> {
-        let ListObjectsV2Input {
+        self.validate_operation(req.credentials.as_ref(), "ListObjectsV2")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
+        let access_key = req
+            .credentials
+            .as_ref()
+            .map(|credentials| credentials.access_key.clone());
+        let output = self.try_list_objects_v2(access_key, req.input).await?;
+
+        Ok(S3Response::new(output))
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_encryption(
+        &self,
+        req: S3Request<PutBucketEncryptionInput>,
+    ) -> S3Result<S3Response<PutBucketEncryptionOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "PutBucketEncryption")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let PutBucketEncryptionInput {
             bucket,
-            prefix,
-            max_keys,
-            start_after,
-            delimiter,
-            encoding_type,
-            continuation_token,
+            server_side_encryption_configuration,
             ..
         } = req.input;
 
-        let max_keys = max_keys.unwrap_or(1000).clamp(0, 1000);
-        let max_keys_usize = try_!(usize::try_from(max_keys));
-        let continuation_token_clone = continuation_token.clone();
+        self.validate_mutable_bucket(&bucket)?;
 
-        let (key_sizes, next_continuation_token) = match continuation_token {
-            // initial request requires taking a snapshot of the state of the database
-            None => {
-                let prefix_clone = prefix.clone();
-                let start_after_clone = start_after.clone();
-                let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
-                let mut key_sizes = bucket_pool
-                    .interact(move |connection| {
-                        let transaction = connection.transaction()?;
-                        Self::try_list_objects(&transaction, &prefix_clone, &start_after_clone)
-                    })
-                    .await
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+        // the configuration is stored only as objects are not encrypted at rest
+        let rules = server_side_encryption_configuration
+            .rules
+            .into_iter()
+            .map(|rule| {
+                let (sse_algorithm, kms_master_key_id) =
+                    match rule.apply_server_side_encryption_by_default {
+                        Some(ServerSideEncryptionByDefault {
+                            sse_algorithm,
+                            kms_master_key_id,
+                        }) => (Some(sse_algorithm.as_str().to_string()), kms_master_key_id),
+                        None => (None, None),
+                    };
+                EncryptionRule {
+                    sse_algorithm,
+                    kms_master_key_id,
+                    bucket_key_enabled: rule.bucket_key_enabled,
+                }
+            })
+            .collect::<Vec<_>>();
 
-                if key_sizes.len() <= max_keys_usize {
-                    (key_sizes, None)
-                } else {
-                    let remainder = key_sizes.split_off(max_keys_usize);
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_bucket_config(&transaction, ENCRYPTION_BUCKET_CONFIG, Some(&rules))?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+        self.forget_server_side_encryption(&bucket).await;
 
-                    let mut continuation_tokens = self.continuation_tokens.lock().unwrap();
-                    let next_continuation_token = Uuid::new_v4().to_string();
-                    continuation_tokens.insert(
-                        next_continuation_token.clone(),
-                        ContinuationToken {
-                            token: next_continuation_token.clone(),
-                            last_modified: OffsetDateTime::now_utc(),
-                            key_sizes: remainder,
-                        },
-                    );
+        Ok(S3Response::new(PutBucketEncryptionOutput::default()))
+    }
 
-                    (key_sizes, Some(next_continuation_token))
-                }
-            }
-            // subsequent request
-            Some(continuation_token) => {
-                let mut continuation_tokens = self.continuation_tokens.lock().unwrap();
-                let mut continuation_token = match continuation_tokens.remove(&continuation_token) {
-                    Some(continuation_token) => Ok(continuation_token),
-                    None => Err(s3_error!(InvalidToken)),
-                }?;
+    #[tracing::instrument]
+    async fn put_bucket_lifecycle_configuration(
+        &self,
+        req: S3Request<PutBucketLifecycleConfigurationInput>,
+    ) -> S3Result<S3Response<PutBucketLifecycleConfigurationOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "PutBucketLifecycleConfiguration")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
 
-                if continuation_token.key_sizes.len() <= max_keys_usize {
-                    (continuation_token.key_sizes, None)
-                } else {
-                    let remainder = continuation_token.key_sizes.split_off(max_keys_usize);
-                    let key_sizes = std::mem::replace(&mut continuation_token.key_sizes, remainder);
+        let PutBucketLifecycleConfigurationInput {
+            bucket,
+            lifecycle_configuration,
+            ..
+        } = req.input;
 
-                    let continuation_token_clone = continuation_token.token.clone();
-                    continuation_token.last_modified = OffsetDateTime::now_utc();
-                    continuation_tokens
-                        .insert(continuation_token_clone.clone(), continuation_token);
+        self.validate_mutable_bucket(&bucket)?;
 
-                    (key_sizes, Some(continuation_token_clone))
-                }
-            }
-        };
+        let lifecycle_configuration =
+            lifecycle_configuration.ok_or_else(|| s3_error!(MalformedXML))?;
 
-        let objects = key_sizes
+        // only expiration of objects after a number of days matching a key prefix is supported
+        let rules = lifecycle_configuration
+            .rules
             .into_iter()
-            .map(|key_size| {
-                Ok(Object {
-                    key: Some(key_size.key),
-                    last_modified: Some(key_size.last_modified.into()),
-                    size: i64::try_from(key_size.size)?,
-                    e_tag: Some(key_size.md5),
-                    ..Default::default()
+            .map(|rule| {
+                let prefix = match rule.filter {
+                    Some(LifecycleRuleFilter::Prefix(prefix)) => prefix,
+                    None => rule.prefix.unwrap_or_default(),
+                    Some(_) => {
+                        return Err(s3_error!(
+                            NotImplemented,
+                            "only prefix lifecycle rule filters are supported"
+                        ))
+                    }
+                };
+                let expiration_days = match rule.expiration {
+                    Some(LifecycleExpiration {
+                        date: None, days, ..
+                    }) if days >= 0 => days,
+                    _ => {
+                        return Err(s3_error!(
+                            NotImplemented,
+                            "only lifecycle expiration in days is supported"
+                        ))
+                    }
+                };
+                Ok(LifecycleExpirationRule {
+                    id: rule.id,
+                    prefix,
+                    expiration_days,
+                    enabled: rule.status.as_str() == ExpirationStatus::ENABLED,
                 })
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect::<S3Result<Vec<_>>>()?;
 
-        let key_count = try_!(i32::try_from(objects.len()));
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_lifecycle_rules(&transaction, &rules)?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
 
-        let output = ListObjectsV2Output {
-            key_count,
-            max_keys,
-            continuation_token: continuation_token_clone,
-            is_truncated: next_continuation_token.is_some(),
-            contents: Some(objects),
-            delimiter,
-            encoding_type,
-            name: Some(bucket),
-            prefix,
-            start_after,
-            next_continuation_token,
-            ..Default::default()
+        Ok(S3Response::new(
+            PutBucketLifecycleConfigurationOutput::default(),
+        ))
+    }
+
+    #[tracing::instrument]
+    async fn put_bucket_website(
+        &self,
+        req: S3Request<PutBucketWebsiteInput>,
+    ) -> S3Result<S3Response<PutBucketWebsiteOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "PutBucketWebsite")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let PutBucketWebsiteInput {
+            bucket,
+            website_configuration,
+            ..
+        } = req.input;
+
+        self.validate_mutable_bucket(&bucket)?;
+
+        if website_configuration.redirect_all_requests_to.is_some()
+            || website_configuration.routing_rules.is_some()
+        {
+            return Err(s3_error!(
+                NotImplemented,
+                "only index and error documents are supported"
+            ));
+        }
+
+        let website = Website {
+            index_document: website_configuration
+                .index_document
+                .map(|index_document| index_document.suffix),
+            error_document: website_configuration
+                .error_document
+                .map(|error_document| error_document.key),
         };
 
-        Ok(S3Response::new(output))
+        let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_put_bucket_config(&transaction, WEBSITE_BUCKET_CONFIG, Some(&website))?;
+                transaction.commit()
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        Ok(S3Response::new(PutBucketWebsiteOutput::default()))
     }
 
     #[tracing::instrument]
@@ -547,6 +1303,10 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<PutObjectInput>,
     ) -> S3Result<S3Response<PutObjectOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "PutObject")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+        self.validate_expected_bucket_owner(req.input.expected_bucket_owner.as_deref())?;
+
         let PutObjectInput {
             body,
             bucket,
@@ -554,9 +1314,51 @@ impl S3 for Sqlite {
             metadata,
             content_length,
             content_md5,
+            content_type,
             storage_class,
             ..
         } = req.input;
+        let key = self.normalize_key(key);
+        let mut metadata = self.apply_default_metadata(&bucket, metadata);
+
+        // a malformed digest is rejected before the body is read
+        if let Some(ref content_md5) = content_md5 {
+            if is_valid_content_md5(content_md5).not() {
+                return Err(s3_error!(InvalidDigest));
+            }
+        }
+
+        // stored so that a copy keeps the content type of its source
+        let content_type = content_type.map(|content_type| content_type.to_string());
+
+        // in import mode the original timestamp of a migrated object is preserved
+        let last_modified = if self.config.import_mode {
+            metadata
+                .as_mut()
+                .and_then(|metadata| metadata.remove(LAST_MODIFIED_METADATA_KEY))
+                .map(|last_modified| {
+                    // stored in UTC so that timestamps compare in order as text
+                    OffsetDateTime::parse(&last_modified, &Rfc3339)
+                        .map(|last_modified| last_modified.to_offset(UtcOffset::UTC))
+                })
+                .transpose()
+                .map_err(|_| {
+                    s3_error!(
+                        InvalidArgument,
+                        "invalid x-amz-meta-{LAST_MODIFIED_METADATA_KEY} timestamp"
+                    )
+                })?
+        } else {
+            None
+        };
+        let last_modified = last_modified.unwrap_or_else(now_utc_seconds);
+
+        Self::get_expires_at(metadata.as_ref()).map_err(|_| {
+            s3_error!(
+                InvalidArgument,
+                "invalid x-amz-meta-{EXPIRES_AT_METADATA_KEY} timestamp"
+            )
+        })?;
 
         self.validate_mutable_bucket(&bucket)?;
 
@@ -564,17 +1366,15 @@ impl S3 for Sqlite {
             return Err(s3_error!(NoSuchBucket));
         }
 
-        if let Some(ref storage_class) = storage_class {
-            let is_valid = ["STANDARD", "REDUCED_REDUNDANCY"].contains(&storage_class.as_str());
-            if is_valid.not() {
-                return Err(s3_error!(InvalidStorageClass));
-            }
-        }
+        check_storage_class(storage_class.as_ref())?;
 
         let Some(body) = body else {
             return Err(s3_error!(IncompleteBody));
         };
 
+        let max_object_size = self.config.max_object_size;
+        check_content_length(content_length, max_object_size)?;
+
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
 
         // if is directory
@@ -588,38 +1388,48 @@ impl S3 for Sqlite {
                 }
             };
 
+            let key_clone = key.clone();
+            let busy_retries = self.config.busy_retries;
             bucket_pool
                 .interact(move |connection| {
-                    let transaction = connection.transaction()?;
+                    let transaction = Self::try_write_transaction(connection, busy_retries)?;
                     Self::try_put_object(
                         &transaction,
                         KeyValue {
-                            key,
+                            key: key_clone,
                             value: None,
                             size: 0,
                             metadata,
-                            last_modified: OffsetDateTime::now_utc(),
+                            content_type,
+                            storage_class: None,
+                            last_modified,
                             md5: None,
+                            blob: None,
                         },
                     )?;
                     transaction.commit()
                 })
                 .await
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                .map_err(to_s3_error)?
+                .map_err(to_s3_error)?;
+
+            self.object_cache.lock().unwrap().invalidate(&bucket, &key);
 
-            let output = PutObjectOutput::default();
+            let output = PutObjectOutput {
+                server_side_encryption: self.try_get_server_side_encryption(&bucket).await?,
+                ..Default::default()
+            };
             return Ok(S3Response::new(output));
         }
 
         let mut md5_hash = Md5::new();
         let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
         let mut value = Vec::new();
-        let size = copy_bytes(stream, &mut value).await?;
+        let size = copy_bytes(stream, &mut value, max_object_size).await?;
         let md5_bytes = md5_hash.finalize();
         let md5 = hex(md5_bytes);
 
-        // if provided verify content_md5
+        // if provided verify content_md5 matches the body
         if let Some(content_md5) = content_md5 {
             if content_md5 != base64(md5_bytes) {
                 return Err(s3_error!(BadDigest));
@@ -629,28 +1439,48 @@ impl S3 for Sqlite {
         debug!(path = %key, ?size, %md5, "write file");
 
         let md5_clone = md5.clone();
+        let key_clone = key.clone();
+        let blob_dir = self.get_blob_dir(&bucket)?;
+        let blob_threshold = self.config.blob_threshold(Some(&bucket));
+        let no_overwrite = self.config.no_overwrite(Some(&bucket));
+        let busy_retries = self.config.busy_retries;
         bucket_pool
             .interact(move |connection| {
-                let transaction = connection.transaction()?;
-                Self::try_put_object(
+                let transaction = Self::try_write_transaction(connection, busy_retries)?;
+                if no_overwrite && Self::try_get_metadata(&transaction, &key_clone)?.is_some() {
+                    Err(s3_error!(
+                        InvalidRequest,
+                        "the key {key_clone} already exists"
+                    ))?;
+                }
+                let replaced_blob = Self::try_put_object_blob(
                     &transaction,
+                    &blob_dir,
+                    blob_threshold,
                     KeyValue {
-                        key,
+                        key: key_clone,
                         value: Some(value),
                         size,
                         metadata,
-                        last_modified: OffsetDateTime::now_utc(),
+                        content_type,
+                        storage_class: None,
+                        last_modified,
                         md5: Some(md5_clone),
+                        blob: None,
                     },
                 )?;
-                transaction.commit()
+                transaction.commit()?;
+                Self::retire_replaced_blob(&blob_dir, replaced_blob);
+                Ok::<_, Error>(())
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+            .map_err(to_s3_error)??;
+
+        self.object_cache.lock().unwrap().invalidate(&bucket, &key);
 
         let output = PutObjectOutput {
             e_tag: Some(md5),
+            server_side_encryption: self.try_get_server_side_encryption(&bucket).await?,
             ..Default::default()
         };
         Ok(S3Response::new(output))
@@ -661,7 +1491,28 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<CreateMultipartUploadInput>,
     ) -> S3Result<S3Response<CreateMultipartUploadOutput>> {
-        let CreateMultipartUploadInput { bucket, key, .. } = req.input;
+        self.validate_operation(req.credentials.as_ref(), "CreateMultipartUpload")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
+        let CreateMultipartUploadInput {
+            bucket,
+            key,
+            metadata,
+            content_type,
+            storage_class,
+            ..
+        } = req.input;
+        let key = self.normalize_key(key);
+        let metadata = self.apply_default_metadata(&bucket, metadata);
+
+        check_storage_class(storage_class.as_ref())?;
+
+        Self::get_expires_at(metadata.as_ref()).map_err(|_| {
+            s3_error!(
+                InvalidArgument,
+                "invalid x-amz-meta-{EXPIRES_AT_METADATA_KEY} timestamp"
+            )
+        })?;
 
         self.validate_mutable_bucket(&bucket)?;
 
@@ -679,12 +1530,18 @@ impl S3 for Sqlite {
                     &bucket_clone,
                     &key_clone,
                     req.credentials,
+                    MultipartUpload {
+                        metadata,
+                        content_type: content_type.map(|content_type| content_type.to_string()),
+                        storage_class: storage_class
+                            .map(|storage_class| storage_class.as_str().to_string()),
+                    },
                 )?;
                 transaction.commit()
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
 
         let output = CreateMultipartUploadOutput {
             bucket: Some(bucket),
@@ -701,6 +1558,9 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<UploadPartInput>,
     ) -> S3Result<S3Response<UploadPartOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "UploadPart")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
         let UploadPartInput {
             body,
             bucket,
@@ -708,21 +1568,51 @@ impl S3 for Sqlite {
             upload_id,
             part_number,
             content_md5,
+            content_length,
             ..
         } = req.input;
+        let key = self.normalize_key(key);
+
+        self.validate_mutable_bucket(&bucket)?;
+
+        // a malformed digest is rejected before the body is read
+        if let Some(ref content_md5) = content_md5 {
+            if is_valid_content_md5(content_md5).not() {
+                return Err(s3_error!(InvalidDigest));
+            }
+        } else if self.config.require_part_md5 {
+            return Err(s3_error!(
+                InvalidRequest,
+                "Content-MD5 is required for every part of a multipart upload"
+            ));
+        }
+
+        let max_parts = self.config.max_parts;
+        if u32::try_from(part_number)
+            .is_ok_and(|part_number| (1..=max_parts).contains(&part_number))
+            .not()
+        {
+            return Err(s3_error!(
+                InvalidArgument,
+                "Part number must be an integer between 1 and {max_parts}, inclusive"
+            ));
+        }
 
         let body = body.ok_or_else(|| s3_error!(IncompleteBody))?;
         let upload_id = Uuid::parse_str(&upload_id).map_err(|_| s3_error!(InvalidRequest))?;
 
+        let max_object_size = self.config.max_object_size;
+        check_content_length(content_length, max_object_size)?;
+
         let mut md5_hash = Md5::new();
         let stream = body.inspect_ok(|bytes| md5_hash.update(bytes.as_ref()));
         let mut value = Vec::new();
-        copy_bytes(stream, &mut value).await?;
+        copy_bytes(stream, &mut value, max_object_size).await?;
         let md5_bytes = md5_hash.finalize();
         let md5 = hex(md5_bytes);
         let size = try_!(i64::try_from(value.len()));
 
-        // if provided verify content_md5
+        // if provided verify content_md5 matches the body
         if let Some(content_md5) = content_md5 {
             if content_md5 != base64(md5_bytes) {
                 return Err(s3_error!(BadDigest));
@@ -733,9 +1623,7 @@ impl S3 for Sqlite {
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
         bucket_pool
             .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let transaction = connection.transaction().map_err(to_s3_error)?;
 
                 if Self::try_verify_upload_id(
                     &transaction,
@@ -744,7 +1632,7 @@ impl S3 for Sqlite {
                     &key,
                     req.credentials,
                 )
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+                .map_err(to_s3_error)?
                 .not()
                 {
                     return Err(s3_error!(AccessDenied));
@@ -761,16 +1649,16 @@ impl S3 for Sqlite {
                         md5: Some(md5_clone),
                     },
                 )
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                .map_err(to_s3_error)?;
 
-                transaction
-                    .commit()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))
+                transaction.commit().map_err(to_s3_error)
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+            .map_err(to_s3_error)?
             .map_err(|err| match err.code() {
-                S3ErrorCode::AccessDenied => err,
+                S3ErrorCode::AccessDenied
+                | S3ErrorCode::SlowDown
+                | S3ErrorCode::ServiceUnavailable => err,
                 _ => s3_error!(InternalError),
             })?;
 
@@ -786,23 +1674,36 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<ListPartsInput>,
     ) -> S3Result<S3Response<ListPartsOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "ListParts")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
         let ListPartsInput {
             bucket,
             key,
             upload_id,
+            max_parts,
+            part_number_marker,
             ..
         } = req.input;
+        let key = self.normalize_key(key);
 
         let upload_id = Uuid::parse_str(&upload_id).map_err(|_| s3_error!(InvalidRequest))?;
 
+        let max_parts = max_parts.unwrap_or(1000).clamp(0, 1000);
+        let max_parts_usize = try_!(usize::try_from(max_parts));
+        let marker = part_number_marker
+            .as_deref()
+            .map(str::parse::<i32>)
+            .transpose()
+            .map_err(|_| s3_error!(InvalidArgument, "invalid part-number-marker"))?
+            .unwrap_or_default();
+
         let bucket_clone = bucket.clone();
         let key_clone = key.clone();
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
-        let parts = bucket_pool
+        let mut parts = bucket_pool
             .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let transaction = connection.transaction().map_err(to_s3_error)?;
 
                 if Self::try_verify_upload_id(
                     &transaction,
@@ -811,26 +1712,36 @@ impl S3 for Sqlite {
                     &key_clone,
                     req.credentials,
                 )
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+                .map_err(to_s3_error)?
                 .not()
                 {
                     return Err(s3_error!(AccessDenied));
                 };
 
-                let parts = Self::try_list_multipart(&transaction, upload_id)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                // one more part than requested is read to tell if the listing is truncated
+                let parts =
+                    Self::try_list_multipart(&transaction, upload_id, marker, max_parts_usize + 1)
+                        .map_err(to_s3_error)?;
 
                 drop(transaction);
 
                 Ok(parts)
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+            .map_err(to_s3_error)?
             .map_err(|err| match err.code() {
-                S3ErrorCode::AccessDenied => err,
+                S3ErrorCode::AccessDenied
+                | S3ErrorCode::SlowDown
+                | S3ErrorCode::ServiceUnavailable => err,
                 _ => s3_error!(InternalError),
             })?;
 
+        let is_truncated = parts.len() > max_parts_usize;
+        parts.truncate(max_parts_usize);
+        let next_part_number_marker = is_truncated
+            .then(|| parts.last().map(|part| part.part_number.to_string()))
+            .flatten();
+
         let parts = parts
             .into_iter()
             .map(|part| Part {
@@ -846,6 +1757,10 @@ impl S3 for Sqlite {
             key: Some(key),
             upload_id: Some(upload_id.to_string()),
             parts: Some(parts),
+            max_parts,
+            part_number_marker,
+            next_part_number_marker,
+            is_truncated,
             ..Default::default()
         };
         Ok(S3Response::new(output))
@@ -856,6 +1771,9 @@ impl S3 for Sqlite {
         &self,
         req: S3Request<CompleteMultipartUploadInput>,
     ) -> S3Result<S3Response<CompleteMultipartUploadOutput>> {
+        self.validate_operation(req.credentials.as_ref(), "CompleteMultipartUpload")?;
+        let _permit = self.acquire_bucket_permit(&req.input.bucket).await?;
+
         let CompleteMultipartUploadInput {
             multipart_upload,
             bucket,
@@ -863,6 +1781,7 @@ impl S3 for Sqlite {
             upload_id,
             ..
         } = req.input;
+        let key = self.normalize_key(key);
 
         self.validate_mutable_bucket(&bucket)?;
 
@@ -872,23 +1791,45 @@ impl S3 for Sqlite {
             return Err(s3_error!(InvalidPart));
         };
 
-        let mut cnt: i32 = 0;
+        // part numbers may have gaps but must be unique and in ascending order
+        let mut part_numbers = Vec::new();
+        let mut seen_part_numbers = HashSet::new();
         for part in multipart_upload.parts.into_iter().flatten() {
             let part_number = part.part_number;
-            cnt += 1;
-            if part_number != cnt {
-                return Err(s3_error!(InvalidRequest, "invalid part order"));
+            if seen_part_numbers.insert(part_number).not() {
+                return Err(s3_error!(
+                    InvalidPart,
+                    "part number {part_number} is listed more than once"
+                ));
+            }
+            if part_numbers
+                .last()
+                .is_some_and(|last_part_number| part_number < *last_part_number)
+            {
+                return Err(s3_error!(InvalidPartOrder));
             }
+            part_numbers.push(part_number);
+        }
+
+        let max_parts = self.config.max_parts;
+        if part_numbers.len() > try_!(usize::try_from(max_parts)) {
+            return Err(s3_error!(
+                InvalidArgument,
+                "a multipart upload cannot be completed with more than {max_parts} parts"
+            ));
         }
 
         let bucket_clone = bucket.clone();
         let key_clone = key.clone();
+        let blob_dir = self.get_blob_dir(&bucket)?;
+        let blob_threshold = self.config.blob_threshold(Some(&bucket));
+        let busy_retries = self.config.busy_retries;
+        let max_object_size = self.config.max_object_size;
         let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
         let md5 = bucket_pool
             .interact(move |connection| {
-                let transaction = connection
-                    .transaction()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let transaction =
+                    Self::try_write_transaction(connection, busy_retries).map_err(to_s3_error)?;
 
                 if Self::try_verify_upload_id(
                     &transaction,
@@ -897,54 +1838,79 @@ impl S3 for Sqlite {
                     &key_clone,
                     req.credentials,
                 )
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+                .map_err(to_s3_error)?
                 .not()
                 {
                     return Err(s3_error!(AccessDenied));
                 };
 
-                let parts = Self::try_get_multiparts(&transaction, upload_id)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                let multipart_upload =
+                    Self::try_get_multipart_upload(&transaction, upload_id).map_err(to_s3_error)?;
 
-                let value = parts
+                let mut parts = Self::try_get_multiparts(&transaction, upload_id)
+                    .map_err(to_s3_error)?
                     .into_iter()
-                    .map(|part| part.value)
-                    .collect::<Vec<_>>()
+                    .map(|part| (part.part_number, part.value))
+                    .collect::<HashMap<_, _>>();
+
+                // only the listed parts make up the object. any other uploaded parts are discarded
+                let value = part_numbers
+                    .into_iter()
+                    .map(|part_number| {
+                        parts.remove(&part_number).ok_or_else(|| {
+                            s3_error!(InvalidPart, "part number {part_number} was not uploaded")
+                        })
+                    })
+                    .collect::<S3Result<Vec<_>>>()?
                     .concat();
+                let size = try_!(u64::try_from(value.len()));
+                if let Some(max_object_size) = max_object_size {
+                    if size > max_object_size {
+                        return Err(entity_too_large(max_object_size));
+                    }
+                }
                 let mut md5_hash = Md5::new();
                 md5_hash.update(&value);
                 let md5 = hex(md5_hash.finalize());
-                let size = try_!(u64::try_from(value.len()));
 
-                Self::try_put_object(
+                let replaced_blob = Self::try_put_object_blob(
                     &transaction,
+                    &blob_dir,
+                    blob_threshold,
                     KeyValue {
                         key: key_clone,
                         value: Some(value),
                         size,
-                        metadata: None,
-                        last_modified: OffsetDateTime::now_utc(),
+                        metadata: multipart_upload.metadata,
+                        content_type: multipart_upload.content_type,
+                        storage_class: multipart_upload.storage_class,
+                        last_modified: now_utc_seconds(),
                         md5: Some(md5.clone()),
+                        blob: None,
                     },
                 )
-                .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                .map_err(S3Error::from)?;
 
-                Self::try_delete_multipart(&transaction, upload_id)
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                Self::try_delete_multipart(&transaction, upload_id).map_err(to_s3_error)?;
 
-                transaction
-                    .commit()
-                    .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?;
+                transaction.commit().map_err(to_s3_error)?;
+                Self::retire_replaced_blob(&blob_dir, replaced_blob);
 
                 Ok(md5)
             })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?
+            .map_err(to_s3_error)?
             .map_err(|err| match err.code() {
-                S3ErrorCode::AccessDenied => err,
+                S3ErrorCode::AccessDenied
+                | S3ErrorCode::InvalidPart
+                | S3ErrorCode::EntityTooLarge
+                | S3ErrorCode::SlowDown
+                | S3ErrorCode::ServiceUnavailable => err,
                 _ => s3_error!(InternalError),
             })?;
 
+        self.object_cache.lock().unwrap().invalidate(&bucket, &key);
+
         let output = CompleteMultipartUploadOutput {
             bucket: Some(bucket),
             key: Some(key),
@@ -954,3 +1920,159 @@ impl S3 for Sqlite {
         Ok(S3Response::new(output))
     }
 }
+
+impl Sqlite {
+    /// list the objects of a bucket on behalf of the `access_key` of the request, if any
+    ///
+    /// A continuation token can only be resumed by the access key which started the listing.
+    async fn try_list_objects_v2(
+        &self,
+        access_key: Option<String>,
+        input: ListObjectsV2Input,
+    ) -> S3Result<ListObjectsV2Output> {
+        let ListObjectsV2Input {
+            bucket,
+            prefix,
+            max_keys,
+            start_after,
+            delimiter,
+            encoding_type,
+            continuation_token,
+            ..
+        } = input;
+
+        // the requesting access key is logged for audit
+        info!(
+            "bucket {bucket} listed by access key {}",
+            access_key.as_deref().unwrap_or("anonymous")
+        );
+
+        // the prefix and start-after are echoed back as requested rather than normalized
+        let request_prefix = prefix.clone();
+        let request_start_after = start_after.clone();
+        let prefix = prefix.map(|prefix| self.normalize_key(prefix));
+        let start_after = start_after.map(|start_after| self.normalize_key(start_after));
+
+        // a max-keys of 0 returns no keys but still reports if the listing is truncated
+        if max_keys.is_some_and(|max_keys| max_keys < 0) {
+            return Err(s3_error!(InvalidArgument, "max-keys cannot be negative"));
+        }
+        let max_keys = max_keys.unwrap_or(1000).min(1000);
+        let max_keys_usize = try_!(usize::try_from(max_keys));
+        let continuation_token_clone = continuation_token.clone();
+
+        let (entries, next_continuation_token) = match continuation_token {
+            // initial request requires taking a snapshot of the state of the database
+            None => {
+                let prefix_clone = prefix.clone();
+                let start_after_clone = start_after.clone();
+                let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+                let key_sizes = bucket_pool
+                    .interact(move |connection| {
+                        let transaction = connection.transaction()?;
+                        Self::try_list_objects(
+                            &transaction,
+                            &prefix_clone,
+                            &start_after_clone,
+                            None,
+                        )
+                    })
+                    .await
+                    .map_err(to_s3_error)?
+                    .map_err(to_s3_error)?;
+
+                let mut entries = Self::rollup_common_prefixes(
+                    key_sizes,
+                    prefix.as_deref(),
+                    delimiter.as_deref(),
+                );
+
+                if entries.len() <= max_keys_usize {
+                    (entries, None)
+                } else {
+                    let remainder = entries.split_off(max_keys_usize);
+
+                    let mut continuation_tokens = self.continuation_tokens.lock().unwrap();
+                    let next_continuation_token = Uuid::new_v4().to_string();
+                    continuation_tokens.insert(
+                        next_continuation_token.clone(),
+                        ContinuationToken {
+                            token: next_continuation_token.clone(),
+                            access_key,
+                            last_modified: OffsetDateTime::now_utc(),
+                            entries: remainder,
+                        },
+                    );
+
+                    (entries, Some(next_continuation_token))
+                }
+            }
+            // subsequent request
+            Some(continuation_token) => {
+                let mut continuation_tokens = self.continuation_tokens.lock().unwrap();
+                // a listing cannot be resumed by a different access key to the one which started it
+                let mut continuation_token = match continuation_tokens.get(&continuation_token) {
+                    Some(existing) if existing.access_key == access_key => {
+                        Ok(continuation_tokens.remove(&continuation_token).unwrap())
+                    }
+                    _ => Err(s3_error!(InvalidToken)),
+                }?;
+
+                if continuation_token.entries.len() <= max_keys_usize {
+                    (continuation_token.entries, None)
+                } else {
+                    let remainder = continuation_token.entries.split_off(max_keys_usize);
+                    let entries = std::mem::replace(&mut continuation_token.entries, remainder);
+
+                    let continuation_token_clone = continuation_token.token.clone();
+                    continuation_token.last_modified = OffsetDateTime::now_utc();
+                    continuation_tokens
+                        .insert(continuation_token_clone.clone(), continuation_token);
+
+                    (entries, Some(continuation_token_clone))
+                }
+            }
+        };
+
+        let mut objects = Vec::new();
+        let mut common_prefixes = Vec::new();
+        for entry in entries {
+            match entry {
+                ListEntry::Object(key_size) => objects.push(Object {
+                    // a directory marker reports the ETag of an empty object as in `head_object`
+                    e_tag: key_size
+                        .md5
+                        .or_else(|| key_size.key.ends_with('/').then(|| EMPTY_MD5.to_string())),
+                    key: Some(key_size.key),
+                    last_modified: Some(key_size.last_modified.into()),
+                    size: try_!(i64::try_from(key_size.size)),
+                    ..Default::default()
+                }),
+                ListEntry::CommonPrefix(prefix) => common_prefixes.push(CommonPrefix {
+                    prefix: Some(prefix),
+                }),
+            }
+        }
+
+        // both the objects and the common prefixes count towards the keys of the response
+        let key_count = try_!(i32::try_from(objects.len() + common_prefixes.len()));
+
+        let output = ListObjectsV2Output {
+            key_count,
+            max_keys,
+            continuation_token: continuation_token_clone,
+            is_truncated: next_continuation_token.is_some(),
+            contents: Some(objects),
+            common_prefixes: Some(common_prefixes),
+            delimiter,
+            encoding_type,
+            name: Some(bucket),
+            prefix: request_prefix,
+            start_after: request_start_after,
+            next_continuation_token,
+            ..Default::default()
+        };
+
+        Ok(output)
+    }
+}
@@ -1,33 +1,182 @@
+use crate::cache::ObjectCache;
 use crate::error::*;
-use crate::utils::repeat_vars;
+use crate::utils::{encode_uri_path, hex, normalize_key, now_utc_seconds, repeat_vars};
 
-use deadpool_sqlite::rusqlite::Transaction;
-use deadpool_sqlite::{Config, Runtime};
+use deadpool_sqlite::rusqlite::{Connection, ErrorCode, Transaction, TransactionBehavior};
+use deadpool_sqlite::{Config, Hook, HookError, Runtime};
 use deadpool_sqlite::{Object, Pool};
+use futures::{stream, Stream, TryStreamExt};
+use md5::{Digest, Md5};
 use path_absolutize::Absolutize;
+use rusqlite::config::DbConfig;
 use rusqlite::Error::ToSqlConversionFailure;
 use rusqlite::{OptionalExtension, ToSql};
 use s3s::auth::Credentials;
 use s3s::S3ErrorCode::{InternalError, MethodNotAllowed};
-use s3s::{dto, s3_error, S3Error};
+use s3s::{dto, s3_error, S3Error, S3ErrorCode, S3Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::ops::Not;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::{self, Not};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use time::{Duration, OffsetDateTime};
+use std::time::Instant;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime, UtcOffset};
 use tokio::fs;
-use tokio::sync::RwLock;
-use tracing::warn;
+use tokio::sync::{OwnedRwLockReadGuard, OwnedSemaphorePermit, RwLock, Semaphore};
+use tracing::{debug, warn};
 use uuid::Uuid;
 
-#[derive(Debug)]
+/// The lifecycle sweep deleting the objects whose keys start with the prefix `?1` of a rule and
+/// which were last modified before `?2`. Public so that the test suite can check its query plan.
+pub const EXPIRE_OBJECTS_QUERY: &str = "
+    DELETE FROM data
+    WHERE key IN (
+        SELECT key
+        FROM metadata
+        WHERE substr(key, 1, length(?1)) = ?1
+        AND last_modified < ?2
+    );";
+
+/// The response header marking a `get_object` answered with the error document of a website.
+pub const ERROR_DOCUMENT_HEADER: &str = "x-s3ite-error-document";
+
+/// Blob files not referenced by any object are only removed once they are older than this so
+/// that the file of a write which has not yet committed, or of a replaced object which may still be
+/// read from an older snapshot, is not removed.
+const ORPHANED_BLOB_AGE: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// How often the maintenance process removes orphaned blob files.
+const ORPHANED_BLOB_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// The number of objects read per query by `Sqlite::iter_objects`.
+const ITER_OBJECTS_PAGE_SIZE: usize = 1000;
+
+/// Schema migrations applied in order on top of the tables created by `try_create_tables`.
+/// The number of applied migrations is tracked in the SQLite `user_version` pragma.
+const MIGRATIONS: &[&str] = &[
+    "
+    ALTER TABLE metadata ADD COLUMN content_type TEXT;
+    ALTER TABLE multipart_upload ADD COLUMN metadata TEXT;
+    ALTER TABLE multipart_upload ADD COLUMN content_type TEXT;
+    ",
+    "
+    CREATE TABLE IF NOT EXISTS lifecycle (
+        id TEXT,
+        prefix TEXT NOT NULL,
+        expiration_days INTEGER NOT NULL,
+        enabled BOOLEAN NOT NULL
+    );
+    ",
+    "
+    ALTER TABLE metadata ADD COLUMN blob TEXT;
+    ",
+    "
+    CREATE TABLE IF NOT EXISTS bucket_config (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    ",
+    "
+    CREATE INDEX IF NOT EXISTS metadata_last_modified ON metadata (last_modified);
+    ",
+    "
+    ALTER TABLE metadata ADD COLUMN expires_at TEXT;
+    CREATE INDEX IF NOT EXISTS metadata_expires_at ON metadata (expires_at) WHERE expires_at IS NOT NULL;
+    ",
+    "
+    CREATE INDEX IF NOT EXISTS metadata_md5_pending ON metadata (key) WHERE md5 IS NULL;
+    ",
+    "
+    ALTER TABLE metadata ADD COLUMN storage_class TEXT;
+    ALTER TABLE multipart_upload ADD COLUMN storage_class TEXT;
+    ",
+];
+
+/// The initial delay in milliseconds before retrying a write transaction on a busy database.
+const BUSY_RETRY_BACKOFF_MS: u64 = 10;
+
+/// The user metadata key holding the RFC 3339 time after which an object is expired.
+pub(crate) const EXPIRES_AT_METADATA_KEY: &str = "s3ite-expires-at";
+
+/// The `bucket_config` entry holding the default encryption rules of a bucket.
+pub(crate) const ENCRYPTION_BUCKET_CONFIG: &str = "encryption";
+
+/// The `bucket_config` entry holding the website configuration of a bucket.
+pub(crate) const WEBSITE_BUCKET_CONFIG: &str = "website";
+
+#[derive(Debug, Clone)]
 pub struct Sqlite {
     pub(crate) root: PathBuf,
     pub(crate) config: crate::Config,
-    pub(crate) buckets: Arc<RwLock<HashMap<String, Pool>>>,
+    pub(crate) buckets: Arc<RwLock<HashMap<String, BucketPool>>>,
     pub(crate) continuation_tokens: Arc<Mutex<HashMap<String, ContinuationToken>>>,
+    pub(crate) object_cache: Arc<Mutex<ObjectCache>>,
+    pub(crate) read_only_overrides: Arc<Mutex<HashMap<String, ReadOnlyOverride>>>,
+    pub(crate) quiesced_buckets: Arc<Mutex<HashSet<String>>>,
+    /// when the maintenance process last removed orphaned blob files
+    pub(crate) last_orphan_sweep: Arc<Mutex<Option<Instant>>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct BucketPool {
+    pub(crate) pool: Pool,
+    pub(crate) creation_date: OffsetDateTime,
+    pub(crate) wal_stats: Option<WalStats>,
+    pub(crate) semaphore: Option<Arc<Semaphore>>,
+    pub(crate) gate: Arc<RwLock<()>>,
+    /// when a connection was last checked out, or `None` once the idle connections have been closed
+    pub(crate) last_access: Arc<Mutex<Option<Instant>>>,
+    /// the algorithm of the default encryption once read so that it is not queried by every
+    /// request, or `None` until it is read again after it may have changed
+    pub(crate) server_side_encryption: Arc<RwLock<Option<DefaultEncryption>>>,
+}
+
+/// A `set_bucket_read_only` override of the configured `read_only` of a bucket.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadOnlyOverride {
+    pub(crate) read_only: bool,
+    /// connections opened before the override was set still have the previous `query_only`
+    pub(crate) changed_at: Instant,
+}
+
+/// The permits held by an S3 operation against a bucket until it completes.
+#[derive(Debug)]
+pub(crate) struct BucketPermit {
+    _concurrency: Option<OwnedSemaphorePermit>,
+    _gate: Option<OwnedRwLockReadGuard<()>>,
+}
+
+/// The connection pool state of a bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The maximum number of connections the pool will open.
+    pub max_size: usize,
+    /// The number of connections currently open.
+    pub size: usize,
+    /// The number of open connections not checked out by a request.
+    pub available: usize,
+    /// The number of requests waiting for a connection.
+    pub waiting: usize,
+}
+
+/// The write-ahead log state of a bucket as reported by `PRAGMA wal_checkpoint`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalStats {
+    /// The number of frames in the WAL when the checkpoint ran.
+    pub log_frames: u64,
+    /// The number of frames that were checkpointed back into the database.
+    pub checkpointed_frames: u64,
+    /// The approximate size of the WAL in bytes when the checkpoint ran.
+    pub size: u64,
+    /// When the checkpoint ran.
+    pub last_checkpoint: OffsetDateTime,
 }
 
 #[derive(Debug)]
@@ -36,23 +185,69 @@ pub(crate) struct KeyValue {
     pub(crate) value: Option<Vec<u8>>,
     pub(crate) size: u64,
     pub(crate) metadata: Option<dto::Metadata>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) storage_class: Option<String>,
     pub(crate) last_modified: OffsetDateTime,
     pub(crate) md5: Option<String>,
+    /// the name of the file in the blob directory holding the value if stored outside the database
+    pub(crate) blob: Option<String>,
 }
 
+/// The listing details of an object.
 #[derive(Debug)]
-pub(crate) struct KeySize {
-    pub(crate) key: String,
-    pub(crate) size: u64,
-    pub(crate) last_modified: OffsetDateTime,
-    pub(crate) md5: String,
+pub struct KeySize {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: OffsetDateTime,
+    /// `None` for a directory marker or an object imported with `lazy_etags` until its ETag is
+    /// computed
+    pub md5: Option<String>,
 }
 
 #[derive(Debug)]
 pub(crate) struct KeyMetadata {
     pub(crate) size: u64,
     pub(crate) metadata: Option<dto::Metadata>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) storage_class: Option<String>,
     pub(crate) last_modified: OffsetDateTime,
+    pub(crate) md5: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct LifecycleExpirationRule {
+    pub(crate) id: Option<String>,
+    pub(crate) prefix: String,
+    pub(crate) expiration_days: i32,
+    pub(crate) enabled: bool,
+}
+
+/// The default encryption of a bucket as read from its `encryption` configuration.
+#[derive(Debug, Clone)]
+pub(crate) struct DefaultEncryption {
+    pub(crate) sse_algorithm: Option<String>,
+}
+
+/// A default encryption rule stored by `put_bucket_encryption`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct EncryptionRule {
+    pub(crate) sse_algorithm: Option<String>,
+    pub(crate) kms_master_key_id: Option<String>,
+    pub(crate) bucket_key_enabled: bool,
+}
+
+/// The documents stored by `put_bucket_website`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Website {
+    pub(crate) index_document: Option<String>,
+    pub(crate) error_document: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct MultipartUpload {
+    pub(crate) metadata: Option<dto::Metadata>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) storage_class: Option<String>,
 }
 
 #[derive(Debug)]
@@ -72,11 +267,19 @@ pub(crate) struct MultipartMetadata {
     pub(crate) size: i64,
 }
 
+/// An entry of a `list_objects` snapshot once keys are rolled up by the delimiter.
+#[derive(Debug)]
+pub(crate) enum ListEntry {
+    Object(KeySize),
+    CommonPrefix(String),
+}
+
 #[derive(Debug)]
 pub(crate) struct ContinuationToken {
     pub(crate) token: String,
+    pub(crate) access_key: Option<String>,
     pub(crate) last_modified: OffsetDateTime,
-    pub(crate) key_sizes: Vec<KeySize>,
+    pub(crate) entries: Vec<ListEntry>,
 }
 
 impl Sqlite {
@@ -85,6 +288,7 @@ impl Sqlite {
         let root = env::current_dir()?.join(&config.root).canonicalize()?;
 
         let mut buckets = HashMap::new();
+        let read_only_overrides = Arc::new(Mutex::new(HashMap::new()));
 
         let mut iter = fs::read_dir(root.clone()).await?;
         while let Some(entry) = iter.next_entry().await? {
@@ -98,12 +302,32 @@ impl Sqlite {
                         let bucket = path.file_stem().unwrap().to_str().unwrap().to_string();
                         let bucket_clone = bucket.clone();
 
-                        let cfg = Config::new(path.clone());
-                        let pool = cfg.create_pool(Runtime::Tokio1)?;
+                        let pool = Self::try_create_pool(
+                            &config,
+                            &read_only_overrides,
+                            &bucket,
+                            path.clone(),
+                        )?;
                         let connection = pool.get().await.unwrap();
-                        connection
+                        let adopt_bare_buckets = config.adopt_bare_buckets;
+                        let bare = connection
                             .interact(move |connection| {
-                                connection.execute_batch(&config.to_sql(Some(&bucket_clone)))?;
+                                let transaction = connection.transaction()?;
+                                let bare = !Self::try_has_tables(&transaction)?;
+                                if bare {
+                                    if !config.adopt_bare_buckets {
+                                        return Ok(true);
+                                    }
+                                    // a database created elsewhere may carry an unrelated user_version
+                                    Self::try_create_tables(&transaction)?;
+                                    transaction.pragma_update(None, "user_version", 0)?;
+                                }
+                                Self::try_migrate(&transaction, &config, Some(&bucket_clone))?;
+                                Self::try_delete_multipart_expired(
+                                    &transaction,
+                                    OffsetDateTime::now_utc().saturating_sub(Duration::hours(1)),
+                                )?;
+                                transaction.commit()?;
 
                                 connection.execute_batch(
                                     "
@@ -111,84 +335,214 @@ impl Sqlite {
                                     PRAGMA optimize;
                                     ",
                                 )?;
-
-                                let transaction = connection.transaction()?;
-                                Self::try_delete_multipart_expired(
-                                    &transaction,
-                                    OffsetDateTime::now_utc().saturating_sub(Duration::hours(1)),
-                                )?;
-                                transaction.commit()
+                                Ok::<_, rusqlite::Error>(bare)
                             })
                             .await
                             .map_err(|_| rusqlite::Error::InvalidQuery)??;
-
-                        buckets.insert(bucket, pool);
+                        if bare {
+                            if !adopt_bare_buckets {
+                                Err(S3Error::with_message(
+                                    InternalError,
+                                    format!(
+                                        "{path:?} does not contain the s3ite tables, set adopt_bare_buckets to create them"
+                                    ),
+                                ))?;
+                            }
+                            warn!("created the s3ite tables in {path:?} to serve it as bucket {bucket}");
+                        }
+
+                        let creation_date = Self::try_get_creation_date(&path).await?;
+                        let semaphore = config
+                            .bucket_concurrency_limit(&bucket)
+                            .map(|limit| Arc::new(Semaphore::new(usize::from(limit))));
+                        buckets.insert(
+                            bucket,
+                            BucketPool {
+                                pool,
+                                creation_date,
+                                wal_stats: None,
+                                semaphore,
+                                gate: Arc::new(RwLock::new(())),
+                                last_access: Arc::new(Mutex::new(Some(Instant::now()))),
+                                server_side_encryption: Arc::default(),
+                            },
+                        );
                     }
                 }
             }
         }
 
-        // validate that any specified bucket configurations have existing bucket
-        let difference = config
-            .buckets
-            .keys()
-            .collect::<HashSet<_>>()
-            .difference(&buckets.keys().collect::<HashSet<_>>())
-            .copied()
-            .collect::<Vec<_>>();
-        if difference.is_empty().not() {
-            Err(S3Error::with_message(
-                InternalError,
-                format!("found configurations for buckets: {difference:?} that do not exist"),
-            ))?;
-        }
-
-        let buckets = Arc::new(RwLock::new(buckets));
-        let continuation_tokens = Arc::new(Mutex::new(HashMap::<String, ContinuationToken>::new()));
+        let sqlite = Self {
+            root,
+            config: config.clone(),
+            buckets: Arc::new(RwLock::new(buckets)),
+            continuation_tokens: Arc::new(Mutex::new(HashMap::new())),
+            object_cache: Arc::new(Mutex::new(ObjectCache::new(
+                config.object_cache_capacity,
+                config.object_cache_size,
+                config.object_cache_object_size,
+            ))),
+            read_only_overrides,
+            quiesced_buckets: Arc::new(Mutex::new(HashSet::new())),
+            last_orphan_sweep: Arc::new(Mutex::new(None)),
+        };
 
         // start a garbage collection process for:
         // - run the vacuum process
+        // - expiring objects by the bucket lifecycle rules
+        // - removing blob files no longer referenced by any object
         // - cleaning up expired continuation_tokens
-        let buckets_clone = buckets.clone();
-        let continuation_tokens_clone = continuation_tokens.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::time::sleep(std::time::Duration::from_millis(10000)).await;
-
-                // database maintenance
-                let buckets = buckets_clone.write().await;
-                for pool in buckets.values() {
-                    let connection = pool.get().await.unwrap();
-                    connection
-                        .interact(move |connection| {
-                            connection
-                                .execute_batch(
-                                    "
-                                    PRAGMA wal_checkpoint(TRUNCATE);
-                                    PRAGMA incremental_vacuum(100);
-                                    ",
-                                )
+        if config.background_maintenance {
+            let sqlite_clone = sqlite.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(std::time::Duration::from_millis(10000)).await;
+                    sqlite_clone.run_maintenance().await;
+                }
+            });
+        }
+
+        Ok(sqlite)
+    }
+
+    /// Run a single pass of the maintenance process over every bucket: expiring objects, collecting
+    /// orphaned blobs, checkpointing the WAL and incrementally vacuuming.
+    ///
+    /// This runs every ten seconds in the background unless `background_maintenance` is disabled, in
+    /// which case an embedder should call it on their own schedule.
+    ///
+    /// # Panics
+    pub async fn run_maintenance(&self) {
+        let wal_warning_size = self.config.wal_warning_size;
+        let vacuum_freelist_percent = self.config.vacuum_freelist_percent;
+
+        // database maintenance
+        let idle_timeout = self.config.idle_timeout.map(std::time::Duration::from_secs);
+        let mut fragmented_buckets = Vec::new();
+        let mut buckets = self.buckets.write().await;
+        for (name, bucket) in buckets.iter_mut() {
+            // a bucket closed for being idle is maintained again once it is next accessed
+            let Some(last_access) = *bucket.last_access.lock().unwrap() else {
+                continue;
+            };
+
+            let read_only = self.read_only(name);
+            let connection = bucket.pool.get().await.unwrap();
+            let (wal_stats, expired, fragmented) = connection
+                .interact(move |connection| {
+                    // object expiration is a mutation so is skipped for read-only buckets
+                    let expired = if read_only {
+                        0
+                    } else {
+                        connection
+                            .transaction()
+                            .and_then(|transaction| {
+                                let expired = Self::try_expire_objects(
+                                    &transaction,
+                                    OffsetDateTime::now_utc(),
+                                )?;
+                                transaction.commit()?;
+                                Ok(expired)
+                            })
+                            .map_err(|err| warn!("{}", err.to_string()))
+                            .unwrap_or_default()
+                    };
+                    // a full `VACUUM` rewrites the whole database so it runs once the buckets are released
+                    let fragmented = match vacuum_freelist_percent {
+                        Some(percent) if read_only.not() => {
+                            Self::try_is_fragmented(connection, percent)
                                 .map_err(|err| warn!("{}", err.to_string()))
-                                .ok();
-                        })
-                        .await
+                                .unwrap_or_default()
+                        }
+                        _ => false,
+                    };
+                    let wal_stats = Self::try_checkpoint(connection)
+                        .map_err(|err| warn!("{}", err.to_string()))
+                        .ok();
+                    connection
+                        .execute_batch("PRAGMA incremental_vacuum(100);")
+                        .map_err(|err| warn!("{}", err.to_string()))
                         .ok();
+                    (wal_stats, expired, fragmented)
+                })
+                .await
+                .unwrap_or_default();
+
+            if expired != 0 {
+                debug!("bucket {name} expired {expired} objects");
+                self.object_cache.lock().unwrap().invalidate_bucket(name);
+            }
+
+            if fragmented {
+                fragmented_buckets.push(name.clone());
+            }
+
+            if let Some(wal_stats) = wal_stats {
+                if wal_warning_size != 0 && wal_stats.size > wal_warning_size {
+                    warn!(
+                        "bucket {name} WAL of {} bytes ({} frames, {} checkpointed) exceeds wal_warning_size of {wal_warning_size} bytes",
+                        wal_stats.size, wal_stats.log_frames, wal_stats.checkpointed_frames,
+                    );
                 }
+                bucket.wal_stats = Some(wal_stats);
+            }
 
-                // remove any redundant state (i.e. cancelled `list_objects` request snapshots)
-                let mut continuation_tokens = continuation_tokens_clone.lock().unwrap();
-                continuation_tokens.retain(|_, value| {
-                    (OffsetDateTime::now_utc() - value.last_modified).as_seconds_f32() < 120.0
-                });
+            // close the connections of a bucket which has not been accessed within the idle timeout now
+            // that its WAL has been checkpointed. deadpool opens new connections on the next access.
+            if idle_timeout.is_some_and(|idle_timeout| last_access.elapsed() >= idle_timeout) {
+                drop(connection);
+                bucket.pool.retain(|_, _| false);
+                *bucket.last_access.lock().unwrap() = None;
+                debug!("bucket {name} closed idle connections");
             }
-        });
+        }
+        drop(buckets);
+
+        // each bucket is vacuumed while only its own requests are held back. the checkpoint then
+        // truncates the database file.
+        if let Some(percent) = vacuum_freelist_percent {
+            for name in fragmented_buckets {
+                let vacuumed = self
+                    .with_bucket_quiesced(&name, move |connection| {
+                        let vacuumed = Self::try_vacuum_fragmented(connection, percent)?;
+                        if vacuumed {
+                            Self::try_checkpoint(connection)?;
+                        }
+                        Ok(vacuumed)
+                    })
+                    .await;
+                match vacuumed {
+                    Ok(true) => debug!("bucket {name} vacuumed"),
+                    Ok(false) => {}
+                    Err(err) => warn!("bucket {name} failed to vacuum: {err:?}"),
+                }
+            }
+        }
 
-        Ok(Self {
-            root,
-            config: config.clone(),
-            buckets,
-            continuation_tokens,
-        })
+        // objects imported with `lazy_etags` are stored without an ETag
+        if self.config.lazy_etags {
+            self.compute_pending_etags().await;
+        }
+
+        let sweep_orphaned_blobs = {
+            let mut last_orphan_sweep = self.last_orphan_sweep.lock().unwrap();
+            let due = last_orphan_sweep.is_none_or(|last_orphan_sweep| {
+                last_orphan_sweep.elapsed() >= ORPHANED_BLOB_SWEEP_INTERVAL
+            });
+            if due {
+                *last_orphan_sweep = Some(Instant::now());
+            }
+            due
+        };
+        if sweep_orphaned_blobs {
+            self.collect_orphaned_blobs().await;
+        }
+
+        // remove any redundant state (i.e. cancelled `list_objects` request snapshots)
+        let mut continuation_tokens = self.continuation_tokens.lock().unwrap();
+        continuation_tokens.retain(|_, value| {
+            (OffsetDateTime::now_utc() - value.last_modified).as_seconds_f32() < 120.0
+        });
     }
 
     pub(crate) fn resolve_abs_path(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
@@ -201,6 +555,74 @@ impl Sqlite {
         self.resolve_abs_path(dir)
     }
 
+    /// resolve the directory holding the values stored outside the bucket database
+    pub(crate) fn get_blob_dir(&self, bucket: &str) -> Result<PathBuf> {
+        let dir = PathBuf::from_str(&format!("{}.blobs", &bucket))?;
+        self.resolve_abs_path(dir)
+    }
+
+    /// write `value` to a new file in `blob_dir` if it is larger than `blob_threshold` returning the
+    /// value to store in the database and the name of the blob file
+    pub(crate) fn try_store_value(
+        blob_dir: &Path,
+        blob_threshold: Option<u64>,
+        value: Vec<u8>,
+    ) -> std::io::Result<(Option<Vec<u8>>, Option<String>)> {
+        match blob_threshold {
+            Some(blob_threshold) if value.len() as u64 > blob_threshold => {
+                std::fs::create_dir_all(blob_dir)?;
+                let blob = Uuid::new_v4().to_string();
+                std::fs::write(blob_dir.join(&blob), value)?;
+                Ok((None, Some(blob)))
+            }
+            _ => Ok((Some(value), None)),
+        }
+    }
+
+    /// read the value of an object stored outside the database
+    pub(crate) fn try_load_value(blob_dir: &Path, kv: &mut KeyValue) -> std::io::Result<()> {
+        if let Some(blob) = &kv.blob {
+            kv.value = Some(std::fs::read(blob_dir.join(blob))?);
+        }
+        Ok(())
+    }
+
+    /// put `kv` writing its value to a new file in `blob_dir` if it is larger than `blob_threshold`.
+    /// returns the blob of any replaced object which should be removed once committed.
+    pub(crate) fn try_put_object_blob(
+        transaction: &Transaction,
+        blob_dir: &Path,
+        blob_threshold: Option<u64>,
+        mut kv: KeyValue,
+    ) -> Result<Option<String>> {
+        let (value, blob) = Self::try_store_value(
+            blob_dir,
+            blob_threshold,
+            kv.value.take().unwrap_or_default(),
+        )?;
+        kv.value = value;
+        kv.blob = blob;
+
+        let replaced_blob = Self::try_get_blob(transaction, &kv.key)?;
+        Self::try_put_object(transaction, kv)?;
+
+        Ok(replaced_blob)
+    }
+
+    /// leave the blob of a replaced or deleted object for the maintenance process to collect as an
+    /// orphaned blob once the change is committed. a reader of an older snapshot may still be
+    /// reading the file so its modified time is reset to keep it for `ORPHANED_BLOB_AGE`.
+    pub(crate) fn retire_replaced_blob(blob_dir: &Path, blob: Option<String>) {
+        if let Some(blob) = blob {
+            std::fs::File::options()
+                .write(true)
+                .open(blob_dir.join(blob))
+                .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+                .map_err(|err| warn!("{}", err.to_string()))
+                .ok();
+        }
+    }
+
     pub(crate) async fn try_create_bucket(
         &self,
         bucket: &str,
@@ -208,26 +630,518 @@ impl Sqlite {
     ) -> rusqlite::Result<()> {
         let config = self.config.clone();
 
-        let cfg = Config::new(file_path);
-        let pool = cfg.create_pool(Runtime::Tokio1).unwrap();
+        let pool =
+            Self::try_create_pool(&config, &self.read_only_overrides, bucket, file_path).unwrap();
         let connection = pool.get().await.unwrap();
 
         connection
             .interact(move |connection| {
-                connection.execute_batch(&config.to_sql(None))?;
-
                 let transaction = connection.transaction()?;
                 Self::try_create_tables(&transaction)?;
+                Self::try_migrate(&transaction, &config, None)?;
                 transaction.commit()
             })
             .await
             .map_err(|_| rusqlite::Error::InvalidQuery)??;
 
-        self.buckets.write().await.insert(bucket.to_string(), pool);
+        let semaphore = self
+            .config
+            .bucket_concurrency_limit(bucket)
+            .map(|limit| Arc::new(Semaphore::new(usize::from(limit))));
+        self.buckets.write().await.insert(
+            bucket.to_string(),
+            BucketPool {
+                pool,
+                creation_date: OffsetDateTime::now_utc(),
+                wal_stats: None,
+                semaphore,
+                gate: Arc::new(RwLock::new(())),
+                last_access: Arc::new(Mutex::new(Some(Instant::now()))),
+                server_side_encryption: Arc::default(),
+            },
+        );
 
         Ok(())
     }
 
+    /// create a pool to the database of `bucket` which applies the configured pragmas to every
+    /// connection it opens, as they are set per connection rather than stored in the database.
+    /// a connection opened before `set_bucket_read_only` last changed the bucket is discarded
+    /// rather than reused so that every connection has its `query_only`.
+    fn try_create_pool(
+        config: &crate::Config,
+        read_only_overrides: &Arc<Mutex<HashMap<String, ReadOnlyOverride>>>,
+        bucket: &str,
+        file_path: PathBuf,
+    ) -> Result<Pool> {
+        let cfg = Config::new(Self::get_open_path(config, file_path));
+        let config = config.clone();
+        let bucket = bucket.to_string();
+        let bucket_clone = bucket.clone();
+        let read_only_overrides = read_only_overrides.clone();
+        let read_only_overrides_clone = read_only_overrides.clone();
+
+        Ok(cfg
+            .builder(Runtime::Tokio1)?
+            .post_create(Hook::async_fn(move |connection, _| {
+                let config = config.clone();
+                let bucket = bucket.clone();
+                let read_only = read_only_overrides
+                    .lock()
+                    .unwrap()
+                    .get(&bucket)
+                    .map(|read_only_override| read_only_override.read_only);
+                Box::pin(async move {
+                    connection
+                        .interact(move |connection| {
+                            Self::try_apply_pragmas(connection, &config, Some(&bucket))?;
+                            if let Some(read_only) = read_only {
+                                connection
+                                    .execute_batch(&format!("PRAGMA query_only={read_only};"))?;
+                            }
+                            Ok::<_, rusqlite::Error>(())
+                        })
+                        .await
+                        .map_err(|err| HookError::Message(err.to_string()))?
+                        .map_err(HookError::Backend)
+                })
+            }))
+            .pre_recycle(Hook::sync_fn(move |_, metrics| {
+                let changed_at = read_only_overrides_clone
+                    .lock()
+                    .unwrap()
+                    .get(&bucket_clone)
+                    .map(|read_only_override| read_only_override.changed_at);
+                if changed_at.is_some_and(|changed_at| metrics.created < changed_at) {
+                    return Err(HookError::StaticMessage(
+                        "the query_only of the connection is stale",
+                    ));
+                }
+                Ok(())
+            }))
+            .build()?)
+    }
+
+    /// apply the configured pragmas to a new connection. SQLite leaves the `journal_mode` unchanged
+    /// rather than failing if it cannot be switched, for example out of `WAL` while another
+    /// connection is open, so the effective mode is compared to the requested one.
+    pub(crate) fn try_apply_pragmas(
+        connection: &Connection,
+        config: &crate::Config,
+        bucket: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        connection.execute_batch(&config.to_sql(bucket))?;
+        connection.set_db_config(
+            DbConfig::SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE,
+            config.checkpoint_on_close.not(),
+        )?;
+
+        let requested = format!("{:?}", config.journal_mode(bucket));
+        let effective =
+            connection.query_row("PRAGMA journal_mode;", (), |row| row.get::<_, String>(0))?;
+        if effective.eq_ignore_ascii_case(&requested).not() {
+            warn!(
+                "bucket {} requested journal_mode {requested} but is using {}",
+                bucket.unwrap_or_default(),
+                effective.to_uppercase()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// begin a transaction which takes the write lock of the database immediately so that a busy or
+    /// locked database is found before any work is done. nothing has been done when it fails so the
+    /// transaction is retried up to `retries` times with a jittered exponential backoff before the
+    /// error is returned.
+    pub(crate) fn try_write_transaction(
+        connection: &Connection,
+        retries: u32,
+    ) -> rusqlite::Result<Transaction<'_>> {
+        let mut attempt = 0;
+        loop {
+            match Transaction::new_unchecked(connection, TransactionBehavior::Immediate) {
+                Err(err)
+                    if attempt < retries
+                        && matches!(
+                            err.sqlite_error_code(),
+                            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+                        ) =>
+                {
+                    let backoff = BUSY_RETRY_BACKOFF_MS << attempt.min(10);
+                    let jitter = RandomState::new().build_hasher().finish() % backoff;
+                    debug!("database is busy, retrying in {}ms", backoff + jitter);
+                    std::thread::sleep(std::time::Duration::from_millis(backoff + jitter));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// the filename to open a bucket database with. shared-cache mode is selected with a URI
+    /// filename which rusqlite opens by default.
+    fn get_open_path(config: &crate::Config, file_path: PathBuf) -> PathBuf {
+        if config.shared_cache {
+            PathBuf::from(format!(
+                "file:{}?cache=shared",
+                encode_uri_path(&file_path.to_string_lossy())
+            ))
+        } else {
+            file_path
+        }
+    }
+
+    /// open a pool to an existing bucket file checking that a connection can be opened
+    pub(crate) async fn try_open_bucket_pool(
+        &self,
+        bucket: &str,
+        file_path: PathBuf,
+    ) -> Result<Pool> {
+        let pool =
+            Self::try_create_pool(&self.config, &self.read_only_overrides, bucket, file_path)?;
+        drop(pool.get().await.map_err(to_s3_error)?);
+
+        Ok(pool)
+    }
+
+    /// remove the `-wal` and `-shm` sidecars before the database file itself so that a failure
+    /// never leaves a sidecar without its database
+    pub(crate) async fn try_remove_bucket_files(
+        bucket_path: &Path,
+        blob_dir: &Path,
+    ) -> std::io::Result<()> {
+        for suffix in ["-wal", "-shm"] {
+            match fs::remove_file(format!("{}{suffix}", bucket_path.to_string_lossy())).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        fs::remove_file(bucket_path).await?;
+
+        // the database is already gone so failing to remove the blobs must not fail the deletion
+        match fs::remove_dir_all(blob_dir).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                warn!("failed to remove {}: {err}", blob_dir.to_string_lossy());
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// remove the orphaned blob files of every writable bucket. each bucket is swept while holding
+    /// its gate rather than the buckets lock so that requests to the other buckets are not blocked.
+    async fn collect_orphaned_blobs(&self) {
+        // a bucket closed for being idle is swept again once it is next accessed
+        let buckets = self
+            .buckets
+            .read()
+            .await
+            .iter()
+            .filter(|(_, bucket)| bucket.last_access.lock().unwrap().is_some())
+            .map(|(name, bucket)| (name.clone(), bucket.pool.clone(), bucket.gate.clone()))
+            .collect::<Vec<_>>();
+
+        for (name, pool, gate) in buckets {
+            if self.read_only(&name) {
+                continue;
+            }
+
+            let _gate = gate.read_owned().await;
+            match self.try_collect_orphaned_blobs(&name, &pool).await {
+                Ok(removed) if removed != 0 => {
+                    debug!("bucket {name} removed {removed} orphaned blobs");
+                }
+                Ok(_) => {}
+                Err(err) => warn!("bucket {name} failed to remove orphaned blobs: {err:?}"),
+            }
+        }
+    }
+
+    /// remove the files in the blob directory of `bucket` which are not referenced by any object.
+    /// recently modified files are kept as they may belong to a write which has not yet committed
+    /// or to a replaced object which is still being read.
+    async fn try_collect_orphaned_blobs(&self, bucket: &str, pool: &Pool) -> Result<usize> {
+        // a bucket deleted while waiting for its gate no longer has a blob directory
+        let blob_dir = self.get_blob_dir(bucket)?;
+        if blob_dir.exists().not() {
+            return Ok(0);
+        }
+
+        let blobs = pool
+            .get()
+            .await
+            .map_err(to_s3_error)?
+            .interact(|connection| {
+                let transaction = connection.transaction()?;
+                Self::try_list_blobs(&transaction)
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        let mut removed = 0;
+        let mut iter = fs::read_dir(&blob_dir).await?;
+        while let Some(entry) = iter.next_entry().await? {
+            let blob = entry.file_name().to_string_lossy().to_string();
+            let age = entry
+                .metadata()
+                .await?
+                .modified()?
+                .elapsed()
+                .unwrap_or_default();
+            if blobs.contains(&blob).not() && age > ORPHANED_BLOB_AGE {
+                fs::remove_file(entry.path()).await?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// compute the ETags of the objects stored without one in every open bucket which is not
+    /// read-only. like the orphan sweep only the bucket being processed is held back from
+    /// `delete_bucket` and `with_bucket_quiesced`.
+    async fn compute_pending_etags(&self) {
+        let batch_size = self.config.import_batch_size.max(1);
+
+        // a bucket closed for being idle is processed again once it is next accessed
+        let buckets = self
+            .buckets
+            .read()
+            .await
+            .iter()
+            .filter(|(_, bucket)| bucket.last_access.lock().unwrap().is_some())
+            .map(|(name, bucket)| (name.clone(), bucket.pool.clone(), bucket.gate.clone()))
+            .collect::<Vec<_>>();
+
+        for (name, pool, gate) in buckets {
+            if self.read_only(&name) {
+                continue;
+            }
+
+            let _gate = gate.read_owned().await;
+            match self
+                .try_compute_pending_etags(&name, &pool, batch_size)
+                .await
+            {
+                Ok(etags) if etags != 0 => {
+                    debug!("bucket {name} computed the ETags of {etags} objects");
+                    self.object_cache.lock().unwrap().invalidate_bucket(&name);
+                }
+                Ok(_) => {}
+                Err(err) => warn!("bucket {name} failed to compute ETags: {err:?}"),
+            }
+        }
+    }
+
+    /// compute and store the ETag of up to `limit` objects which were stored without one. directory
+    /// markers are skipped as they never have one. the values are hashed one at a time in a read
+    /// transaction so that writers are not blocked, and an ETag is only stored if its object has
+    /// not been replaced in the meantime.
+    async fn try_compute_pending_etags(
+        &self,
+        bucket: &str,
+        pool: &Pool,
+        limit: usize,
+    ) -> Result<usize> {
+        // a bucket deleted while waiting for its gate no longer has a database
+        if self.get_bucket_path(bucket)?.exists().not() {
+            return Ok(0);
+        }
+
+        let blob_dir = self.get_blob_dir(bucket)?;
+        let busy_retries = self.config.busy_retries;
+        pool.get()
+            .await
+            .map_err(to_s3_error)?
+            .interact(move |connection| {
+                let etags = {
+                    let transaction = connection.transaction()?;
+                    let pending = transaction
+                        .prepare_cached(
+                            "
+                            SELECT key, blob, size, last_modified
+                            FROM metadata
+                            WHERE md5 IS NULL
+                            AND substr(key, -1) <> '/'
+                            LIMIT ?;",
+                        )?
+                        .query_map([limit], |row| {
+                            Ok((
+                                row.get::<_, String>(0)?,
+                                row.get::<_, Option<String>>(1)?,
+                                row.get::<_, u64>(2)?,
+                                row.get::<_, rusqlite::types::Value>(3)?,
+                            ))
+                        })?
+                        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+                    let mut etags = Vec::with_capacity(pending.len());
+                    for (key, blob, size, last_modified) in pending {
+                        let md5 =
+                            Self::try_hash_value(&transaction, &blob_dir, &key, blob.as_deref())?;
+                        etags.push((key, md5, blob, size, last_modified));
+                    }
+                    etags
+                };
+                if etags.is_empty() {
+                    return Ok(0);
+                }
+
+                let transaction = Self::try_write_transaction(connection, busy_retries)?;
+                let mut computed = 0;
+                {
+                    let mut stmt = transaction.prepare_cached(
+                        "
+                        UPDATE metadata
+                        SET md5 = ?2
+                        WHERE key = ?1
+                        AND md5 IS NULL
+                        AND blob IS ?3
+                        AND size = ?4
+                        AND last_modified = ?5;",
+                    )?;
+                    for (key, md5, blob, size, last_modified) in &etags {
+                        computed += stmt.execute((key, md5, blob, size, last_modified))?;
+                    }
+                }
+                transaction.commit()?;
+                Ok::<_, Error>(computed)
+            })
+            .await
+            .map_err(to_s3_error)?
+    }
+
+    /// the MD5 of the value of `key` read in chunks from its blob file, if it has one, or from the
+    /// database
+    fn try_hash_value(
+        transaction: &Transaction,
+        blob_dir: &Path,
+        key: &str,
+        blob: Option<&str>,
+    ) -> Result<String> {
+        let mut md5_hash = Md5::new();
+        match blob {
+            Some(blob) => {
+                let mut file = std::fs::File::open(blob_dir.join(blob))?;
+                let mut buffer = vec![0; 64 * 1024];
+                loop {
+                    let read = file.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    md5_hash.update(&buffer[..read]);
+                }
+            }
+            None => {
+                let value = transaction
+                    .prepare_cached("SELECT value FROM data WHERE key = ?;")?
+                    .query_row([key], |row| row.get::<_, Option<Vec<u8>>>(0))?;
+                md5_hash.update(value.unwrap_or_default());
+            }
+        }
+        Ok(hex(md5_hash.finalize()))
+    }
+
+    /// whether more than `percent` of the pages of the database are free
+    fn try_is_fragmented(connection: &rusqlite::Connection, percent: u8) -> rusqlite::Result<bool> {
+        let page_count =
+            connection.query_row("PRAGMA page_count;", (), |row| row.get::<_, u64>(0))?;
+        let freelist_count =
+            connection.query_row("PRAGMA freelist_count;", (), |row| row.get::<_, u64>(0))?;
+
+        Ok(freelist_count * 100 > page_count * u64::from(percent))
+    }
+
+    /// run a full `VACUUM` if the database is still fragmented. the whole database is rewritten so
+    /// this is run while the bucket is quiesced.
+    fn try_vacuum_fragmented(
+        connection: &rusqlite::Connection,
+        percent: u8,
+    ) -> rusqlite::Result<bool> {
+        if Self::try_is_fragmented(connection, percent)?.not() {
+            return Ok(false);
+        }
+
+        connection.execute_batch("VACUUM;")?;
+        Ok(true)
+    }
+
+    /// checkpoint and truncate the WAL returning its state prior to the checkpoint
+    fn try_checkpoint(connection: &rusqlite::Connection) -> rusqlite::Result<WalStats> {
+        let page_size =
+            connection.query_row("PRAGMA page_size;", (), |row| row.get::<_, u64>(0))?;
+
+        // `wal_checkpoint` returns -1 for the frame counts if the database is not in WAL mode
+        let (log_frames, checkpointed_frames) =
+            connection.query_row("PRAGMA wal_checkpoint(TRUNCATE);", (), |row| {
+                Ok((row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+            })?;
+        let log_frames = u64::try_from(log_frames).unwrap_or_default();
+        let checkpointed_frames = u64::try_from(checkpointed_frames).unwrap_or_default();
+
+        Ok(WalStats {
+            log_frames,
+            checkpointed_frames,
+            size: log_frames * page_size,
+            last_checkpoint: OffsetDateTime::now_utc(),
+        })
+    }
+
+    /// The WAL state of `bucket` recorded by the most recent maintenance checkpoint or `None` if
+    /// no checkpoint has run yet.
+    pub async fn wal_stats(&self, bucket: &str) -> Result<Option<WalStats>> {
+        Ok(self
+            .buckets
+            .read()
+            .await
+            .get(bucket)
+            .ok_or_else(|| s3_error!(NoSuchBucket))?
+            .wal_stats)
+    }
+
+    /// The current connection pool state of `bucket`. A growing number of `waiting` requests
+    /// indicates the bucket cannot keep up with the requests made to it.
+    pub async fn pool_stats(&self, bucket: &str) -> Result<PoolStats> {
+        let status = self
+            .buckets
+            .read()
+            .await
+            .get(bucket)
+            .ok_or_else(|| s3_error!(NoSuchBucket))?
+            .pool
+            .status();
+
+        Ok(PoolStats {
+            max_size: status.max_size,
+            size: status.size,
+            available: status.available,
+            waiting: status.waiting,
+        })
+    }
+
+    /// the file creation date if supported by the filesystem otherwise the last modified date
+    async fn try_get_creation_date(path: &Path) -> Result<OffsetDateTime> {
+        let file_meta = fs::metadata(path).await?;
+        Ok(file_meta
+            .created()
+            .or_else(|_| file_meta.modified())?
+            .into())
+    }
+
+    /// whether the `data` and `metadata` tables of a bucket exist
+    fn try_has_tables(transaction: &Transaction) -> rusqlite::Result<bool> {
+        transaction
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('data', 'metadata')",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count == 2)
+    }
+
     /// resolve object path under the virtual root
     pub(crate) fn try_create_tables(transaction: &Transaction) -> rusqlite::Result<usize> {
         transaction.execute(
@@ -274,16 +1188,399 @@ impl Sqlite {
         )
     }
 
+    /// apply any outstanding `MIGRATIONS` to the database
+    pub(crate) fn try_migrate(
+        transaction: &Transaction,
+        config: &crate::Config,
+        bucket: Option<&str>,
+    ) -> rusqlite::Result<()> {
+        let user_version =
+            transaction.query_row("PRAGMA user_version;", (), |row| row.get::<_, usize>(0))?;
+
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(user_version) {
+            transaction.execute_batch(migration)?;
+            transaction.execute_batch(&format!("PRAGMA user_version={};", version + 1))?;
+        }
+
+        // optional schema changes are applied only once their feature is enabled
+        if config.track_created_at {
+            Self::try_migrate_created_at(transaction)?;
+        }
+        if config.key_search(bucket) {
+            Self::try_migrate_key_search(transaction)?;
+        }
+
+        Ok(())
+    }
+
+    /// add a trigram FTS5 index over the object keys which triggers on `metadata` keep up to date.
+    /// FTS5 needs an integer rowid so the keys are numbered in `key_search_keys`. the keys of objects
+    /// which existed before are indexed when it is added.
+    fn try_migrate_key_search(transaction: &Transaction) -> rusqlite::Result<()> {
+        transaction.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS key_search_keys (
+                id INTEGER PRIMARY KEY,
+                key TEXT NOT NULL UNIQUE
+            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS key_search USING fts5(
+                key,
+                content='key_search_keys',
+                content_rowid='id',
+                tokenize='trigram'
+            );
+            CREATE TRIGGER IF NOT EXISTS key_search_keys_insert AFTER INSERT ON key_search_keys
+            BEGIN
+                INSERT INTO key_search (rowid, key) VALUES (NEW.id, NEW.key);
+            END;
+            CREATE TRIGGER IF NOT EXISTS key_search_keys_delete AFTER DELETE ON key_search_keys
+            BEGIN
+                INSERT INTO key_search (key_search, rowid, key) VALUES ('delete', OLD.id, OLD.key);
+            END;
+            CREATE TRIGGER IF NOT EXISTS metadata_key_search_insert AFTER INSERT ON metadata
+            BEGIN
+                INSERT OR IGNORE INTO key_search_keys (key) VALUES (NEW.key);
+            END;
+            CREATE TRIGGER IF NOT EXISTS metadata_key_search_update AFTER UPDATE OF key ON metadata
+            WHEN OLD.key != NEW.key
+            BEGIN
+                DELETE FROM key_search_keys WHERE key = OLD.key;
+                INSERT OR IGNORE INTO key_search_keys (key) VALUES (NEW.key);
+            END;
+            CREATE TRIGGER IF NOT EXISTS metadata_key_search_delete AFTER DELETE ON metadata
+            BEGIN
+                DELETE FROM key_search_keys WHERE key = OLD.key;
+            END;
+            INSERT OR IGNORE INTO key_search_keys (key) SELECT key FROM metadata;
+            ",
+        )
+    }
+
+    pub(crate) fn try_search_keys(
+        transaction: &Transaction,
+        query: &str,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<KeySize>> {
+        let limit = i64::try_from(limit).map_err(|err| ToSqlConversionFailure(err.into()))?;
+
+        // the query is quoted as a single FTS5 string so that it matches as a substring of the key
+        let query = format!("\"{}\"", query.replace('"', "\"\""));
+
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT metadata.key, metadata.size, metadata.last_modified, metadata.md5
+            FROM key_search
+            INNER JOIN metadata ON metadata.key = key_search.key
+            WHERE key_search MATCH ?1
+            ORDER BY metadata.key
+            LIMIT ?2;",
+        )?;
+
+        let objects = stmt
+            .query_map((query, limit), |row| {
+                Ok(KeySize {
+                    key: row.get(0)?,
+                    size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    md5: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(objects)
+    }
+
+    /// add a `created_at` column which a trigger sets when an object is first inserted so that it
+    /// is kept when the object is overwritten. objects which existed before are left `NULL`.
+    fn try_migrate_created_at(transaction: &Transaction) -> rusqlite::Result<()> {
+        let exists = transaction.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('metadata') WHERE name = 'created_at';",
+            (),
+            |row| row.get::<_, bool>(0),
+        )?;
+
+        if exists.not() {
+            transaction.execute_batch(
+                "
+                ALTER TABLE metadata ADD COLUMN created_at TEXT;
+                CREATE TRIGGER IF NOT EXISTS metadata_created_at AFTER INSERT ON metadata
+                BEGIN
+                    UPDATE metadata SET created_at = NEW.last_modified WHERE key = NEW.key;
+                END;
+                ",
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// the time `key` was first written if `track_created_at` was enabled when it was
+    pub(crate) fn try_get_created_at(
+        transaction: &Transaction,
+        key: &str,
+    ) -> rusqlite::Result<Option<OffsetDateTime>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT created_at
+            FROM metadata
+            WHERE key = ?;",
+        )?;
+
+        Ok(stmt
+            .query_row([key], |row| row.get::<_, Option<OffsetDateTime>>(0))
+            .optional()?
+            .flatten())
+    }
+
     pub(crate) async fn try_get_bucket_pool(&self, bucket: &str) -> Result<Object> {
-        Ok(self
+        // the read guard is held until a connection is checked out so that `delete_bucket` can wait
+        // for every request using the bucket
+        let buckets = self.buckets.read().await;
+        let bucket_pool = buckets.get(bucket).ok_or_else(|| s3_error!(NoSuchBucket))?;
+        let pool = &bucket_pool.pool;
+        let reopened = bucket_pool
+            .last_access
+            .lock()
+            .unwrap()
+            .replace(Instant::now())
+            .is_none();
+
+        let pool_warning_waiting = self.config.pool_warning_waiting;
+        let waiting = pool.status().waiting;
+        if pool_warning_waiting != 0 && waiting >= pool_warning_waiting {
+            warn!(
+                "bucket {bucket} has {waiting} requests waiting for a connection which exceeds pool_warning_waiting of {pool_warning_waiting}"
+            );
+        }
+
+        // the pool applies the pragmas to the connections it opens to replace the closed ones
+        let connection = pool.get().await.map_err(to_s3_error)?;
+        if reopened {
+            debug!("bucket {bucket} reopened after being idle");
+        }
+
+        Ok(connection)
+    }
+
+    /// the algorithm of the default encryption of a bucket which is reported on its objects. read
+    /// from the bucket database only after it may have changed.
+    pub(crate) async fn try_get_server_side_encryption(
+        &self,
+        bucket: &str,
+    ) -> Result<Option<dto::ServerSideEncryption>> {
+        let server_side_encryption = self
             .buckets
             .read()
             .await
             .get(bucket)
-            .ok_or_else(|| s3_error!(NoSuchBucket))?
-            .get()
+            .map(|bucket_pool| bucket_pool.server_side_encryption.clone())
+            .ok_or_else(|| s3_error!(NoSuchBucket))?;
+        if let Some(default_encryption) = &*server_side_encryption.read().await {
+            return Ok(default_encryption
+                .sse_algorithm
+                .clone()
+                .map(dto::ServerSideEncryption::from));
+        }
+
+        // the write guard is held while reading so that a concurrent change, which forgets the
+        // algorithm once committed, cannot be overwritten by the algorithm read before it
+        let mut server_side_encryption = server_side_encryption.write().await;
+        if let Some(default_encryption) = &*server_side_encryption {
+            return Ok(default_encryption
+                .sse_algorithm
+                .clone()
+                .map(dto::ServerSideEncryption::from));
+        }
+
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        let rules = bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_get_bucket_config::<Vec<EncryptionRule>>(
+                    &transaction,
+                    ENCRYPTION_BUCKET_CONFIG,
+                )
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        let sse_algorithm = rules
+            .into_iter()
+            .flatten()
+            .find_map(|rule| rule.sse_algorithm);
+        *server_side_encryption = Some(DefaultEncryption {
+            sse_algorithm: sse_algorithm.clone(),
+        });
+
+        Ok(sse_algorithm.map(dto::ServerSideEncryption::from))
+    }
+
+    /// read the default encryption of a bucket again on its next use, once a change is committed
+    pub(crate) async fn forget_server_side_encryption(&self, bucket: &str) {
+        let server_side_encryption = self
+            .buckets
+            .read()
+            .await
+            .get(bucket)
+            .map(|bucket_pool| bucket_pool.server_side_encryption.clone());
+        if let Some(server_side_encryption) = server_side_encryption {
+            *server_side_encryption.write().await = None;
+        }
+    }
+
+    /// wait for a permit of the `concurrency_limit` of a bucket, if set, which is held until the
+    /// operation completes. Returns `SlowDown` while the bucket is quiesced by `with_bucket_quiesced`.
+    pub(crate) async fn acquire_bucket_permit(&self, bucket: &str) -> S3Result<BucketPermit> {
+        if self.quiesced_buckets.lock().unwrap().contains(bucket) {
+            return Err(s3_error!(
+                SlowDown,
+                "the bucket {bucket} is unavailable during maintenance"
+            ));
+        }
+
+        let (semaphore, gate) = self
+            .buckets
+            .read()
+            .await
+            .get(bucket)
+            .map(|bucket_pool| {
+                (
+                    bucket_pool.semaphore.clone(),
+                    Some(bucket_pool.gate.clone()),
+                )
+            })
+            .unwrap_or_default();
+
+        let concurrency = match semaphore {
+            Some(semaphore) => semaphore.acquire_owned().await.ok(),
+            None => None,
+        };
+        let gate = match gate {
+            Some(gate) => Some(gate.read_owned().await),
+            None => None,
+        };
+
+        Ok(BucketPermit {
+            _concurrency: concurrency,
+            _gate: gate,
+        })
+    }
+
+    /// hold the gate of a bucket, without a permit of its `concurrency_limit`, while one of its
+    /// connections is used on behalf of an operation on another bucket
+    pub(crate) async fn acquire_bucket_gate(
+        &self,
+        bucket: &str,
+    ) -> Option<OwnedRwLockReadGuard<()>> {
+        let gate = self.buckets.read().await.get(bucket)?.gate.clone();
+        Some(gate.read_owned().await)
+    }
+
+    /// append the index document suffix of the bucket website configuration to a key ending in `/`
+    /// if the `website` option is enabled
+    pub(crate) async fn try_resolve_index_document(
+        &self,
+        bucket: &str,
+        key: String,
+    ) -> S3Result<String> {
+        if self.config.website.not() || key.ends_with('/').not() {
+            return Ok(key);
+        }
+
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        let website = bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_get_bucket_config::<Website>(&transaction, WEBSITE_BUCKET_CONFIG)
+            })
             .await
-            .map_err(|err| S3Error::with_message(InternalError, err.to_string()))?)
+            .map_err(to_s3_error)?
+            .map_err(to_s3_error)?;
+
+        Ok(match website.and_then(|website| website.index_document) {
+            Some(suffix) => format!("{key}{suffix}"),
+            None => key,
+        })
+    }
+
+    /// Answer a `get_object` marked with `ERROR_DOCUMENT_HEADER` with `404 Not Found` as the
+    /// `S3` trait can only set the status of an error. Applied to every response of the service.
+    #[must_use]
+    pub fn map_website_response<B>(mut response: http::Response<B>) -> http::Response<B> {
+        if response
+            .headers_mut()
+            .remove(ERROR_DOCUMENT_HEADER)
+            .is_some()
+        {
+            *response.status_mut() = http::StatusCode::NOT_FOUND;
+        }
+        response
+    }
+
+    /// the object at the error document key of the bucket website configuration which is served in
+    /// place of a missing key if the `website` option is enabled
+    pub(crate) async fn try_get_error_document(&self, bucket: &str) -> S3Result<Option<KeyValue>> {
+        let blob_dir = self.get_blob_dir(bucket)?;
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction().map_err(to_s3_error)?;
+                let error_document =
+                    Self::try_get_bucket_config::<Website>(&transaction, WEBSITE_BUCKET_CONFIG)
+                        .map_err(to_s3_error)?
+                        .and_then(|website| website.error_document);
+                let Some(error_document) = error_document else {
+                    return Ok(None);
+                };
+
+                let object =
+                    Self::try_get_object(&transaction, &error_document).map_err(to_s3_error)?;
+                let Some(mut object) = object else {
+                    return Ok(None);
+                };
+                Self::try_load_value(&blob_dir, &mut object).map_err(to_s3_error)?;
+                Ok(Some(object))
+            })
+            .await
+            .map_err(to_s3_error)?
+    }
+
+    /// read the metadata of `key` from the object cache or the bucket database without reading
+    /// its value
+    pub(crate) async fn try_get_key_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> S3Result<KeyMetadata> {
+        if let Some(object) = self.object_cache.lock().unwrap().get(bucket, key) {
+            return Ok(KeyMetadata {
+                size: object.size,
+                metadata: object.metadata,
+                content_type: object.content_type,
+                storage_class: object.storage_class,
+                last_modified: object.last_modified,
+                md5: object.md5,
+            });
+        }
+
+        let key = key.to_string();
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction().map_err(to_s3_error)?;
+                Self::try_get_metadata(&transaction, &key)
+                    .map_err(to_s3_error)?
+                    .ok_or_else(|| s3_error!(NoSuchKey))
+            })
+            .await
+            .map_err(to_s3_error)?
+            .map_err(|err| match err.code() {
+                S3ErrorCode::NoSuchKey
+                | S3ErrorCode::SlowDown
+                | S3ErrorCode::ServiceUnavailable => err,
+                _ => s3_error!(InternalError),
+            })
     }
 
     /// resolve object path under the virtual root
@@ -291,32 +1588,110 @@ impl Sqlite {
         transaction: &Transaction,
         prefix: &Option<String>,
         start_after: &Option<String>,
+        limit: Option<usize>,
     ) -> rusqlite::Result<Vec<KeySize>> {
-        // prefix with the sqlite wildcard
-        let prefix = prefix.as_ref().and_then(|prefix| {
-            if prefix.is_empty() {
-                None
-            } else {
-                Some(format!("{prefix}%"))
-            }
-        });
+        // an empty prefix lists every key
+        let prefix = prefix.as_ref().filter(|prefix| prefix.is_empty().not());
+
+        // a negative limit is unbounded
+        let limit = limit
+            .map_or(Ok(-1), i64::try_from)
+            .map_err(|err| ToSqlConversionFailure(err.into()))?;
 
         let (query, params): (&str, Vec<&dyn ToSql>) = match (&prefix, start_after) {
             (Some(prefix), Some(start_after)) => (
-                "SELECT key, size, last_modified, md5 FROM metadata WHERE key LIKE ?1 AND key > ?2 ORDER BY key;",
-                vec![prefix, start_after],
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE substr(key, 1, length(?1)) = ?1 AND key > ?2 ORDER BY key LIMIT ?3;",
+                vec![prefix, start_after, &limit],
             ),
             (Some(prefix), None) => (
-                "SELECT key, size, last_modified, md5 FROM metadata WHERE key LIKE ?1 ORDER BY key;",
-                vec![prefix],
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE substr(key, 1, length(?1)) = ?1 ORDER BY key LIMIT ?2;",
+                vec![prefix, &limit],
             ),
             (None, Some(start_after)) => (
-                "SELECT key, size, last_modified, md5 FROM metadata WHERE key > ?1 ORDER BY key;",
-                vec![start_after],
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE key > ?1 ORDER BY key LIMIT ?2;",
+                vec![start_after, &limit],
             ),
             (None, None) => (
-                "SELECT key, size, last_modified, md5 FROM metadata ORDER BY key;",
-                vec![],
+                "SELECT key, size, last_modified, md5 FROM metadata ORDER BY key LIMIT ?1;",
+                vec![&limit],
+            ),
+        };
+
+        let mut stmt = transaction.prepare_cached(query)?;
+
+        let objects = stmt
+            .query_map(params.as_slice(), |row| {
+                Ok(KeySize {
+                    key: row.get(0)?,
+                    size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    md5: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(objects)
+    }
+
+    /// list the objects whose keys start with `prefix` with the most recently modified first using
+    /// the `metadata_last_modified` index
+    pub(crate) fn try_list_objects_by_last_modified(
+        transaction: &Transaction,
+        prefix: &Option<String>,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<KeySize>> {
+        let prefix = prefix.as_ref().filter(|prefix| prefix.is_empty().not());
+        let limit = i64::try_from(limit).map_err(|err| ToSqlConversionFailure(err.into()))?;
+
+        let (query, params): (&str, Vec<&dyn ToSql>) = match &prefix {
+            Some(prefix) => (
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE substr(key, 1, length(?1)) = ?1 ORDER BY last_modified DESC, key LIMIT ?2;",
+                vec![prefix, &limit],
+            ),
+            None => (
+                "SELECT key, size, last_modified, md5 FROM metadata ORDER BY last_modified DESC, key LIMIT ?1;",
+                vec![&limit],
+            ),
+        };
+
+        let mut stmt = transaction.prepare_cached(query)?;
+
+        let objects = stmt
+            .query_map(params.as_slice(), |row| {
+                Ok(KeySize {
+                    key: row.get(0)?,
+                    size: row.get(1)?,
+                    last_modified: row.get(2)?,
+                    md5: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(objects)
+    }
+
+    pub(crate) fn try_list_objects_by_metadata(
+        transaction: &Transaction,
+        name: &str,
+        value: &str,
+        prefix: &Option<String>,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<KeySize>> {
+        let prefix = prefix.as_ref().filter(|prefix| prefix.is_empty().not());
+        let limit = i64::try_from(limit).map_err(|err| ToSqlConversionFailure(err.into()))?;
+
+        // the metadata is stored as a JSON object so the quoted name selects a single user metadata key
+        let name = name.replace('"', "");
+        let path = format!("$.\"{name}\"");
+
+        let (query, params): (&str, Vec<&dyn ToSql>) = match &prefix {
+            Some(prefix) => (
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE json_extract(metadata, ?1) = ?2 AND substr(key, 1, length(?3)) = ?3 ORDER BY key LIMIT ?4;",
+                vec![&path, &value, prefix, &limit],
+            ),
+            None => (
+                "SELECT key, size, last_modified, md5 FROM metadata WHERE json_extract(metadata, ?1) = ?2 ORDER BY key LIMIT ?3;",
+                vec![&path, &value, &limit],
             ),
         };
 
@@ -336,6 +1711,43 @@ impl Sqlite {
         Ok(objects)
     }
 
+    /// roll up the keys which contain `delimiter` after `prefix` into their common prefixes
+    pub(crate) fn rollup_common_prefixes(
+        key_sizes: Vec<KeySize>,
+        prefix: Option<&str>,
+        delimiter: Option<&str>,
+    ) -> Vec<ListEntry> {
+        let Some(delimiter) = delimiter.filter(|delimiter| delimiter.is_empty().not()) else {
+            return key_sizes.into_iter().map(ListEntry::Object).collect();
+        };
+        let prefix = prefix.unwrap_or_default();
+
+        let mut entries = Vec::with_capacity(key_sizes.len());
+        for key_size in key_sizes {
+            match key_size
+                .key
+                .strip_prefix(prefix)
+                .and_then(|remainder| remainder.find(delimiter))
+            {
+                Some(index) => {
+                    let common_prefix = &key_size.key[..prefix.len() + index + delimiter.len()];
+
+                    // keys are sorted so all keys sharing a common prefix are adjacent
+                    let rolled_up = matches!(
+                        entries.last(),
+                        Some(ListEntry::CommonPrefix(last)) if last == common_prefix
+                    );
+                    if rolled_up.not() {
+                        entries.push(ListEntry::CommonPrefix(common_prefix.to_string()));
+                    }
+                }
+                None => entries.push(ListEntry::Object(key_size)),
+            }
+        }
+
+        entries
+    }
+
     /// resolve object path under the virtual root
     pub(crate) fn try_get_object(
         transaction: &Transaction,
@@ -349,35 +1761,120 @@ impl Sqlite {
                 metadata.size,
                 metadata.metadata,
                 metadata.last_modified,
-                metadata.md5
+                metadata.md5,
+                metadata.content_type,
+                metadata.blob,
+                metadata.storage_class
+            FROM metadata
+            INNER JOIN data ON metadata.key = data.key
+            WHERE metadata.key = ?
+            AND (metadata.expires_at IS NULL OR metadata.expires_at > strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'));",
+        )?;
+
+        stmt.query_row([key], |row| {
+            Ok(KeyValue {
+                key: row.get(0)?,
+                value: row.get::<_, Option<Vec<u8>>>(1)?,
+                size: row.get(2)?,
+                metadata: row
+                    .get::<_, Option<String>>(3)?
+                    .map(|metadata| serde_json::from_str(&metadata))
+                    .transpose()
+                    .map_err(|err| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            3,
+                            rusqlite::types::Type::Text,
+                            Box::new(err),
+                        )
+                    })?,
+                content_type: row.get(6)?,
+                storage_class: row.get(8)?,
+                last_modified: row.get(4)?,
+                md5: row.get(5)?,
+                blob: row.get(7)?,
+            })
+        })
+        .optional()
+    }
+
+    /// read the bytes in `range` of the value of `key` from the database or its blob file
+    pub(crate) fn try_get_value_range(
+        transaction: &Transaction,
+        blob_dir: &Path,
+        key: &str,
+        range: &ops::Range<u64>,
+    ) -> Result<Vec<u8>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT
+                substr(data.value, ?2, ?3),
+                metadata.blob
+            FROM metadata
+            INNER JOIN data ON metadata.key = data.key
+            WHERE metadata.key = ?1;",
+        )?;
+
+        let (value, blob) = stmt.query_row(
+            (
+                key,
+                i64::try_from(range.start + 1)?,
+                i64::try_from(range.end - range.start)?,
+            ),
+            |row| {
+                Ok((
+                    row.get::<_, Option<Vec<u8>>>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                ))
+            },
+        )?;
+
+        match blob {
+            Some(blob) => {
+                let mut file = std::fs::File::open(blob_dir.join(blob))?;
+                file.seek(SeekFrom::Start(range.start))?;
+                let mut value = vec![0; usize::try_from(range.end - range.start)?];
+                file.read_exact(&mut value)?;
+                Ok(value)
+            }
+            None => Ok(value.unwrap_or_default()),
+        }
+    }
+
+    pub(crate) fn try_list_blobs(transaction: &Transaction) -> rusqlite::Result<HashSet<String>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT blob
+            FROM metadata
+            WHERE blob IS NOT NULL;",
+        )?;
+
+        let blobs = stmt
+            .query_map((), |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<HashSet<_>>>()?;
+
+        Ok(blobs)
+    }
+
+    /// the name of the blob file holding the value of `key` if it is stored outside the database
+    pub(crate) fn try_get_blob(
+        transaction: &Transaction,
+        key: &str,
+    ) -> rusqlite::Result<Option<String>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT blob
             FROM metadata
-            INNER JOIN data ON metadata.key = data.key
-            WHERE metadata.key = ?;",
+            WHERE key = ?;",
         )?;
 
-        stmt.query_row([key], |row| {
-            Ok(KeyValue {
-                key: row.get(0)?,
-                value: Some(row.get::<_, Vec<u8>>(1)?),
-                size: row.get(2)?,
-                metadata: row
-                    .get::<_, Option<String>>(3)?
-                    .map(|metadata| serde_json::from_str(&metadata))
-                    .transpose()
-                    .map_err(|err| {
-                        rusqlite::Error::FromSqlConversionFailure(
-                            3,
-                            rusqlite::types::Type::Text,
-                            Box::new(err),
-                        )
-                    })?,
-                last_modified: row.get(4)?,
-                md5: row.get(5)?,
-            })
-        })
-        .optional()
+        Ok(stmt
+            .query_row([key], |row| row.get::<_, Option<String>>(0))
+            .optional()?
+            .flatten())
     }
 
+    /// the metadata of `key` unless it is past its `expires_at`. the current time is formatted by
+    /// `strftime` to match how rusqlite stores an `OffsetDateTime` so the two compare as text.
     pub(crate) fn try_get_metadata(
         transaction: &Transaction,
         key: &str,
@@ -387,9 +1884,13 @@ impl Sqlite {
             SELECT
                 size,
                 metadata,
-                last_modified
+                last_modified,
+                content_type,
+                md5,
+                storage_class
             FROM metadata
-            WHERE key = ?;",
+            WHERE key = ?
+            AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'));",
         )?;
 
         stmt.query_row([key], |row| {
@@ -406,7 +1907,10 @@ impl Sqlite {
                             Box::new(err),
                         )
                     })?,
+                content_type: row.get(3)?,
+                storage_class: row.get(5)?,
                 last_modified: row.get(2)?,
+                md5: row.get(4)?,
             })
         })
         .optional()
@@ -429,12 +1933,15 @@ impl Sqlite {
 
         let mut stmt = transaction.prepare_cached(
             "
-            INSERT INTO metadata (key, size, metadata, last_modified, md5)
-            VALUES (?1, ?2, ?3, ?4, ?5)
+            INSERT INTO metadata (key, size, metadata, last_modified, md5, content_type, blob, expires_at, storage_class)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
             ON CONFLICT(key) DO UPDATE
-            SET size=excluded.size, metadata=excluded.metadata, last_modified=excluded.last_modified, md5=excluded.md5;",
+            SET size=excluded.size, metadata=excluded.metadata, last_modified=excluded.last_modified, md5=excluded.md5, content_type=excluded.content_type, blob=excluded.blob, expires_at=excluded.expires_at, storage_class=excluded.storage_class;",
         )?;
 
+        let expires_at = Self::get_expires_at(kv.metadata.as_ref())
+            .map_err(|err| ToSqlConversionFailure(Box::new(err)))?;
+
         stmt.execute((
             kv.key,
             kv.size,
@@ -444,9 +1951,26 @@ impl Sqlite {
                 .map_err(|err| ToSqlConversionFailure(Box::new(err)))?,
             kv.last_modified,
             kv.md5,
+            kv.content_type,
+            kv.blob,
+            expires_at,
+            kv.storage_class,
         ))
     }
 
+    /// the time after which an object is expired from its `x-amz-meta-s3ite-expires-at` metadata
+    pub(crate) fn get_expires_at(
+        metadata: Option<&dto::Metadata>,
+    ) -> std::result::Result<Option<OffsetDateTime>, time::error::Parse> {
+        metadata
+            .and_then(|metadata| metadata.get(EXPIRES_AT_METADATA_KEY))
+            .map(|expires_at| {
+                OffsetDateTime::parse(expires_at, &Rfc3339)
+                    .map(|expires_at| expires_at.to_offset(UtcOffset::UTC))
+            })
+            .transpose()
+    }
+
     /// resolve object path under the virtual root
     pub(crate) fn try_delete_object(
         transaction: &Transaction,
@@ -460,10 +1984,43 @@ impl Sqlite {
         stmt.execute([key])
     }
 
+    /// rename `src_key` to `tgt_key` in place. the foreign key check is deferred to the end of
+    /// the transaction as `metadata` references `data` by key.
+    pub(crate) fn try_rename_object(
+        transaction: &Transaction,
+        src_key: &str,
+        tgt_key: &str,
+    ) -> rusqlite::Result<usize> {
+        transaction.execute_batch("PRAGMA defer_foreign_keys=true;")?;
+
+        let mut stmt = transaction.prepare_cached(
+            "
+            UPDATE data
+            SET key = ?2
+            WHERE key = ?1;",
+        )?;
+        let rows_affected = stmt.execute([src_key, tgt_key])?;
+
+        let mut stmt = transaction.prepare_cached(
+            "
+            UPDATE metadata
+            SET key = ?2
+            WHERE key = ?1;",
+        )?;
+        stmt.execute([src_key, tgt_key])?;
+
+        Ok(rows_affected)
+    }
+
     pub(crate) fn try_delete_objects(
         transaction: &Transaction,
         keys: &[String],
     ) -> rusqlite::Result<Vec<String>> {
+        // `repeat_vars` cannot build an empty `IN` list
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
         let vars = repeat_vars(keys.len());
 
         let mut stmt = transaction.prepare(&format!(
@@ -480,6 +2037,15 @@ impl Sqlite {
         Ok(keys)
     }
 
+    pub(crate) fn try_is_empty(transaction: &Transaction) -> rusqlite::Result<bool> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT NOT EXISTS (SELECT 1 FROM metadata);",
+        )?;
+
+        stmt.query_row((), |row| row.get::<_, bool>(0))
+    }
+
     pub(crate) fn try_delete_objects_like(
         transaction: &Transaction,
         key: &str,
@@ -499,11 +2065,12 @@ impl Sqlite {
         bucket: &str,
         key: &str,
         credentials: Option<Credentials>,
+        multipart_upload: MultipartUpload,
     ) -> rusqlite::Result<usize> {
         let mut stmt = transaction.prepare_cached(
             "
-            INSERT INTO multipart_upload (upload_id, last_modified, bucket, key, access_key)
-            VALUES (?1, ?2, ?3, ?4, ?5);",
+            INSERT INTO multipart_upload (upload_id, last_modified, bucket, key, access_key, metadata, content_type, storage_class)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8);",
         )?;
 
         let now = OffsetDateTime::now_utc();
@@ -513,9 +2080,49 @@ impl Sqlite {
             bucket,
             key,
             credentials.map(|credentials| credentials.access_key),
+            multipart_upload
+                .metadata
+                .map(|metadata| serde_json::to_string(&metadata))
+                .transpose()
+                .map_err(|err| ToSqlConversionFailure(Box::new(err)))?,
+            multipart_upload.content_type,
+            multipart_upload.storage_class,
         ))
     }
 
+    pub(crate) fn try_get_multipart_upload(
+        transaction: &Transaction,
+        upload_id: Uuid,
+    ) -> rusqlite::Result<MultipartUpload> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT
+                metadata,
+                content_type,
+                storage_class
+            FROM multipart_upload
+            WHERE upload_id = ?1;",
+        )?;
+
+        stmt.query_row([upload_id], |row| {
+            Ok(MultipartUpload {
+                metadata: row
+                    .get::<_, Option<String>>(0)?
+                    .map(|metadata| serde_json::from_str(&metadata))
+                    .transpose()
+                    .map_err(|err| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            0,
+                            rusqlite::types::Type::Text,
+                            Box::new(err),
+                        )
+                    })?,
+                content_type: row.get(1)?,
+                storage_class: row.get(2)?,
+            })
+        })
+    }
+
     pub(crate) fn try_verify_upload_id(
         transaction: &Transaction,
         upload_id: Uuid,
@@ -557,10 +2164,15 @@ impl Sqlite {
         ))
     }
 
+    /// list up to `limit` parts of an upload with a part number after `part_number_marker`
     pub(crate) fn try_list_multipart(
         transaction: &Transaction,
         upload_id: Uuid,
+        part_number_marker: i32,
+        limit: usize,
     ) -> rusqlite::Result<Vec<MultipartMetadata>> {
+        let limit = i64::try_from(limit).map_err(|err| ToSqlConversionFailure(err.into()))?;
+
         let mut stmt = transaction.prepare_cached(
             "
             SELECT
@@ -569,12 +2181,14 @@ impl Sqlite {
                 size
             FROM multipart_upload_part
             WHERE upload_id = ?1
-            ORDER BY part_number;",
+            AND part_number > ?2
+            ORDER BY part_number
+            LIMIT ?3;",
         )?;
 
         #[allow(clippy::let_and_return)]
         let objects = stmt
-            .query_map([upload_id], |row| {
+            .query_map((upload_id, part_number_marker, limit), |row| {
                 Ok(MultipartMetadata {
                     last_modified: row.get(0)?,
                     part_number: row.get(1)?,
@@ -648,8 +2262,625 @@ impl Sqlite {
         Ok(())
     }
 
+    /// replace the lifecycle rules of the bucket
+    pub(crate) fn try_put_lifecycle_rules(
+        transaction: &Transaction,
+        rules: &[LifecycleExpirationRule],
+    ) -> rusqlite::Result<()> {
+        transaction.execute("DELETE FROM lifecycle;", ())?;
+
+        let mut stmt = transaction.prepare_cached(
+            "
+            INSERT INTO lifecycle (id, prefix, expiration_days, enabled)
+            VALUES (?1, ?2, ?3, ?4);",
+        )?;
+        for rule in rules {
+            stmt.execute((&rule.id, &rule.prefix, rule.expiration_days, rule.enabled))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn try_get_lifecycle_rules(
+        transaction: &Transaction,
+    ) -> rusqlite::Result<Vec<LifecycleExpirationRule>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT id, prefix, expiration_days, enabled
+            FROM lifecycle
+            ORDER BY ROWID;",
+        )?;
+
+        let rules = stmt
+            .query_map((), |row| {
+                Ok(LifecycleExpirationRule {
+                    id: row.get(0)?,
+                    prefix: row.get(1)?,
+                    expiration_days: row.get(2)?,
+                    enabled: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(rules)
+    }
+
+    /// store `value` as JSON under `name` in the `bucket_config` table or remove it if `None`
+    pub(crate) fn try_put_bucket_config<T: Serialize>(
+        transaction: &Transaction,
+        name: &str,
+        value: Option<&T>,
+    ) -> rusqlite::Result<()> {
+        match value {
+            Some(value) => {
+                let value = serde_json::to_string(value)
+                    .map_err(|err| ToSqlConversionFailure(Box::new(err)))?;
+                transaction
+                    .prepare_cached(
+                        "
+                        INSERT INTO bucket_config (name, value)
+                        VALUES (?1, ?2)
+                        ON CONFLICT(name) DO UPDATE
+                        SET value=excluded.value;",
+                    )?
+                    .execute((name, value))?;
+            }
+            None => {
+                transaction
+                    .prepare_cached("DELETE FROM bucket_config WHERE name = ?;")?
+                    .execute([name])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn try_get_bucket_config<T: DeserializeOwned>(
+        transaction: &Transaction,
+        name: &str,
+    ) -> rusqlite::Result<Option<T>> {
+        let mut stmt = transaction.prepare_cached(
+            "
+            SELECT value
+            FROM bucket_config
+            WHERE name = ?;",
+        )?;
+
+        stmt.query_row([name], |row| row.get::<_, String>(0))
+            .optional()?
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })
+    }
+
+    /// delete the objects which have outlived an enabled lifecycle rule or their `expires_at`. `last_modified` is compared
+    /// directly, rather than through `DATETIME`, so that the `metadata_last_modified` index is used.
+    pub(crate) fn try_expire_objects(
+        transaction: &Transaction,
+        now: OffsetDateTime,
+    ) -> rusqlite::Result<usize> {
+        let rules = Self::try_get_lifecycle_rules(transaction)?;
+
+        let mut stmt = transaction.prepare_cached(EXPIRE_OBJECTS_QUERY)?;
+
+        let mut expired = 0;
+        for rule in rules.iter().filter(|rule| rule.enabled) {
+            let expire_before = now.saturating_sub(Duration::days(rule.expiration_days.into()));
+            expired += stmt.execute((&rule.prefix, expire_before))?;
+        }
+
+        // objects past their own `x-amz-meta-s3ite-expires-at`
+        let mut stmt = transaction.prepare_cached(
+            "
+            DELETE FROM data
+            WHERE key IN (
+                SELECT key
+                FROM metadata
+                WHERE expires_at < ?1
+            );",
+        )?;
+        expired += stmt.execute([now])?;
+
+        Ok(expired)
+    }
+
+    /// Stream the objects in `bucket` whose keys start with `prefix` in key order.
+    ///
+    /// Objects are read in pages of `ITER_OBJECTS_PAGE_SIZE` keys, each in its own transaction, so
+    /// the stream does not hold a consistent snapshot of the bucket: objects written or deleted
+    /// while iterating may or may not be returned.
+    pub fn iter_objects(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+    ) -> impl Stream<Item = Result<KeySize>> + '_ {
+        let bucket = bucket.to_string();
+
+        // the state is the key to continue after or `None` once the last page has been read
+        stream::unfold(Some(None::<String>), move |start_after| {
+            let bucket = bucket.clone();
+            let prefix = prefix.clone();
+            async move {
+                let start_after = start_after?;
+
+                let page = async {
+                    let _permit = self.acquire_bucket_permit(&bucket).await?;
+                    let bucket_pool = self.try_get_bucket_pool(&bucket).await?;
+                    let key_sizes = bucket_pool
+                        .interact(move |connection| {
+                            let transaction = connection.transaction()?;
+                            Self::try_list_objects(
+                                &transaction,
+                                &prefix,
+                                &start_after,
+                                Some(ITER_OBJECTS_PAGE_SIZE),
+                            )
+                        })
+                        .await
+                        .map_err(to_s3_error)?
+                        .map_err(to_s3_error)?;
+                    Ok::<_, Error>(key_sizes)
+                }
+                .await;
+
+                match page {
+                    Ok(key_sizes) => {
+                        let next = (key_sizes.len() == ITER_OBJECTS_PAGE_SIZE)
+                            .then(|| key_sizes.last().map(|key_size| key_size.key.clone()));
+                        Some((Ok(key_sizes), next))
+                    }
+                    Err(err) => Some((Err(err), None)),
+                }
+            }
+        })
+        .map_ok(|key_sizes| stream::iter(key_sizes.into_iter().map(Ok::<_, Error>)))
+        .try_flatten()
+    }
+
+    /// List up to `limit` objects in `bucket` whose keys start with `prefix` with the most recently
+    /// modified first, for reporting. This is not part of the S3 API which always lists in key order.
+    pub async fn list_objects_by_last_modified(
+        &self,
+        bucket: &str,
+        prefix: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<KeySize>> {
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        Ok(bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_list_objects_by_last_modified(&transaction, &prefix, limit)
+            })
+            .await
+            .map_err(to_s3_error)??)
+    }
+
+    /// List up to `limit` objects in `bucket` whose keys start with `prefix` and whose user metadata
+    /// `name`, without the `x-amz-meta-` prefix, is `value` in key order. This is not part of the S3
+    /// API and every object of the bucket is scanned as the metadata is not indexed.
+    pub async fn list_objects_by_metadata(
+        &self,
+        bucket: &str,
+        name: &str,
+        value: &str,
+        prefix: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<KeySize>> {
+        let name = name.to_string();
+        let value = value.to_string();
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        Ok(bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_list_objects_by_metadata(&transaction, &name, &value, &prefix, limit)
+            })
+            .await
+            .map_err(to_s3_error)??)
+    }
+
+    /// List up to `limit` objects in `bucket` whose keys contain `query` anywhere in key order,
+    /// using the FTS5 index added when `key_search` is enabled for the bucket. The `query` must be
+    /// at least three characters. This is not part of the S3 API.
+    pub async fn search_keys(
+        &self,
+        bucket: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<KeySize>> {
+        if self.config.key_search(Some(bucket)).not() {
+            return Err(s3_error!(
+                InvalidRequest,
+                "key_search is not enabled for the bucket {bucket}"
+            )
+            .into());
+        }
+
+        let query = query.to_string();
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        Ok(bucket_pool
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                Self::try_search_keys(&transaction, &query, limit)
+            })
+            .await
+            .map_err(to_s3_error)??)
+    }
+
+    /// Atomically rename `src_key` to `tgt_key` within `bucket` without copying the object.
+    ///
+    /// Returns `InvalidRequest` if `tgt_key` already exists unless `overwrite` is set.
+    ///
+    /// # Panics
+    pub async fn rename_object(
+        &self,
+        bucket: &str,
+        src_key: &str,
+        tgt_key: &str,
+        overwrite: bool,
+    ) -> Result<()> {
+        self.validate_mutable_bucket(bucket)?;
+
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+
+        let src_key = self.normalize_key(src_key.to_string());
+        let tgt_key = self.normalize_key(tgt_key.to_string());
+
+        let src_key_clone = src_key.clone();
+        let tgt_key_clone = tgt_key.clone();
+        let blob_dir = self.get_blob_dir(bucket)?;
+        let busy_retries = self.config.busy_retries;
+        let bucket_pool = self.try_get_bucket_pool(bucket).await?;
+        bucket_pool
+            .interact(move |connection| {
+                let transaction =
+                    Self::try_write_transaction(connection, busy_retries).map_err(to_s3_error)?;
+
+                if src_key_clone == tgt_key_clone {
+                    return Err(s3_error!(
+                        InvalidRequest,
+                        "the source and target keys are the same"
+                    ));
+                }
+
+                let mut replaced_blob = None;
+                if Self::try_get_metadata(&transaction, &tgt_key_clone)
+                    .map_err(to_s3_error)?
+                    .is_some()
+                {
+                    if overwrite.not() {
+                        return Err(s3_error!(
+                            InvalidRequest,
+                            "the target key {tgt_key_clone} already exists"
+                        ));
+                    }
+                    replaced_blob =
+                        Self::try_get_blob(&transaction, &tgt_key_clone).map_err(to_s3_error)?;
+                    Self::try_delete_object(&transaction, &tgt_key_clone).map_err(to_s3_error)?;
+                }
+
+                let rows_affected =
+                    Self::try_rename_object(&transaction, &src_key_clone, &tgt_key_clone)
+                        .map_err(to_s3_error)?;
+                if rows_affected != 1 {
+                    return Err(s3_error!(NoSuchKey));
+                }
+
+                transaction.commit().map_err(to_s3_error)?;
+                Self::retire_replaced_blob(&blob_dir, replaced_blob);
+                Ok(())
+            })
+            .await
+            .map_err(to_s3_error)??;
+
+        let mut object_cache = self.object_cache.lock().unwrap();
+        object_cache.invalidate(bucket, &src_key);
+        object_cache.invalidate(bucket, &tgt_key);
+
+        Ok(())
+    }
+
+    /// Run `f` in a transaction against `bucket` for queries not covered by the S3 API such as
+    /// reporting.
+    ///
+    /// The connection is `query_only` while `f` runs so any attempt to write fails.
+    pub async fn with_reader<F, T>(&self, bucket: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let read_only = self.read_only(bucket);
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+        let connection = self.try_get_bucket_pool(bucket).await?;
+
+        Ok(connection
+            .interact(move |connection| {
+                connection.execute_batch("PRAGMA query_only=true;")?;
+                let result = connection
+                    .transaction()
+                    .and_then(|transaction| f(&transaction));
+                connection.execute_batch(&format!("PRAGMA query_only={read_only};"))?;
+                result
+            })
+            .await
+            .map_err(to_s3_error)??)
+    }
+
+    /// Run `f` in a transaction against `bucket` which is committed if `f` succeeds, for example to
+    /// create a custom index.
+    ///
+    /// Returns `MethodNotAllowed` if the bucket is read-only. As the changes made by `f` are not
+    /// known every cached object of the bucket is invalidated.
+    ///
+    /// # Panics
+    pub async fn with_writer<F, T>(&self, bucket: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.validate_mutable_bucket(bucket)?;
+
+        let _permit = self.acquire_bucket_permit(bucket).await?;
+        let connection = self.try_get_bucket_pool(bucket).await?;
+        let result = connection
+            .interact(move |connection| {
+                let transaction = connection.transaction()?;
+                let result = f(&transaction)?;
+                transaction.commit()?;
+                Ok::<_, rusqlite::Error>(result)
+            })
+            .await
+            .map_err(to_s3_error)??;
+
+        self.object_cache.lock().unwrap().invalidate_bucket(bucket);
+        self.forget_server_side_encryption(bucket).await;
+
+        Ok(result)
+    }
+
+    /// Ingest `(key, value, metadata)` objects into `bucket` without going through the S3 API, for
+    /// example when migrating data.
+    ///
+    /// Objects are written in transactions of `import_batch_size` objects which are each committed
+    /// before the next is started, so an error leaves the earlier batches imported. Returns the number
+    /// of objects imported.
+    ///
+    /// # Panics
+    pub async fn import_objects<I>(&self, bucket: &str, objects: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = (String, Vec<u8>, Option<dto::Metadata>)>,
+        I::IntoIter: Send,
+    {
+        self.validate_mutable_bucket(bucket)?;
+
+        let blob_dir = self.get_blob_dir(bucket)?;
+        let blob_threshold = self.config.blob_threshold(Some(bucket));
+        let no_overwrite = self.config.no_overwrite(Some(bucket));
+        let batch_size = self.config.import_batch_size.max(1);
+        let busy_retries = self.config.busy_retries;
+        let lazy_etags = self.config.lazy_etags;
+
+        let mut objects = objects.into_iter();
+        let mut imported = 0;
+        loop {
+            let last_modified = now_utc_seconds();
+            let batch = objects
+                .by_ref()
+                .take(batch_size)
+                .map(|(key, value, metadata)| KeyValue {
+                    key: self.normalize_key(key),
+                    size: value.len() as u64,
+                    // computed by the maintenance process
+                    md5: lazy_etags.not().then(|| hex(Md5::digest(&value))),
+                    value: Some(value),
+                    metadata,
+                    content_type: None,
+                    storage_class: None,
+                    last_modified,
+                    blob: None,
+                })
+                .collect::<Vec<_>>();
+            if batch.is_empty() {
+                break;
+            }
+            let batch_len = batch.len();
+
+            // a permit is held per batch so that a long import does not hold off `delete_bucket`
+            // or `with_bucket_quiesced` until it has finished
+            let _permit = self.acquire_bucket_permit(bucket).await?;
+            let blob_dir = blob_dir.clone();
+            let connection = self.try_get_bucket_pool(bucket).await?;
+            connection
+                .interact(move |connection| {
+                    let transaction = Self::try_write_transaction(connection, busy_retries)?;
+                    let mut replaced_blobs = Vec::new();
+                    for kv in batch {
+                        if no_overwrite && Self::try_get_metadata(&transaction, &kv.key)?.is_some()
+                        {
+                            Err(s3_error!(
+                                InvalidRequest,
+                                "the key {} already exists",
+                                kv.key
+                            ))?;
+                        }
+                        replaced_blobs.push(Self::try_put_object_blob(
+                            &transaction,
+                            &blob_dir,
+                            blob_threshold,
+                            kv,
+                        )?);
+                    }
+                    transaction.commit()?;
+                    for replaced_blob in replaced_blobs {
+                        Self::retire_replaced_blob(&blob_dir, replaced_blob);
+                    }
+                    Ok::<_, Error>(())
+                })
+                .await
+                .map_err(to_s3_error)??;
+
+            self.object_cache.lock().unwrap().invalidate_bucket(bucket);
+
+            imported += batch_len;
+            debug!(bucket, imported, "imported objects");
+        }
+
+        Ok(imported)
+    }
+
+    /// Freeze or unfreeze writes to `bucket` without restarting.
+    ///
+    /// The override is held in memory only so it is cleared on restart unless `read_only` is also
+    /// set for the bucket in the configuration.
+    ///
+    /// # Panics
+    pub async fn set_bucket_read_only(&self, bucket: &str, read_only: bool) -> Result<()> {
+        let buckets = self.buckets.read().await;
+        let bucket_pool = buckets.get(bucket).ok_or_else(|| s3_error!(NoSuchBucket))?;
+
+        self.read_only_overrides.lock().unwrap().insert(
+            bucket.to_string(),
+            ReadOnlyOverride {
+                read_only,
+                changed_at: Instant::now(),
+            },
+        );
+
+        // the idle connections are closed now and those in use are discarded once returned so that
+        // the pool opens new connections with the `query_only` of the override
+        bucket_pool.pool.retain(|_, _| false);
+
+        Ok(())
+    }
+
+    /// Run `f` against `bucket` once every in-flight S3 operation on it has completed, for example
+    /// an offline `VACUUM` or migration of a single bucket.
+    ///
+    /// New S3 operations on the bucket are rejected with a retryable `SlowDown` until `f` returns
+    /// and the other buckets are not affected. As the changes made by `f` are not known every cached
+    /// object of the bucket is invalidated.
+    ///
+    /// # Panics
+    pub async fn with_bucket_quiesced<F, T>(&self, bucket: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let gate = self
+            .buckets
+            .read()
+            .await
+            .get(bucket)
+            .map(|bucket_pool| bucket_pool.gate.clone())
+            .ok_or_else(|| s3_error!(NoSuchBucket))?;
+
+        self.quiesced_buckets
+            .lock()
+            .unwrap()
+            .insert(bucket.to_string());
+
+        let result: Result<T> = async {
+            // wait for the in-flight operations to release their permits
+            let _gate = gate.write().await;
+            let connection = self.try_get_bucket_pool(bucket).await?;
+            Ok(connection.interact(f).await.map_err(to_s3_error)??)
+        }
+        .await;
+
+        self.quiesced_buckets.lock().unwrap().remove(bucket);
+        self.object_cache.lock().unwrap().invalidate_bucket(bucket);
+        self.forget_server_side_encryption(bucket).await;
+
+        result
+    }
+
+    pub(crate) fn read_only(&self, bucket: &str) -> bool {
+        self.read_only_overrides
+            .lock()
+            .unwrap()
+            .get(bucket)
+            .map(|read_only_override| read_only_override.read_only)
+            .unwrap_or_else(|| self.config.read_only(Some(bucket)))
+    }
+
+    /// merge the `default_metadata` of a bucket into the metadata of an upload without replacing
+    /// any key supplied by the client
+    pub(crate) fn apply_default_metadata(
+        &self,
+        bucket: &str,
+        metadata: Option<dto::Metadata>,
+    ) -> Option<dto::Metadata> {
+        let Some(default_metadata) = self.config.default_metadata(bucket) else {
+            return metadata;
+        };
+
+        let mut metadata = metadata.unwrap_or_default();
+        for (key, value) in default_metadata {
+            metadata
+                .entry(key.to_lowercase())
+                .or_insert_with(|| value.clone());
+        }
+        Some(metadata)
+    }
+
+    /// apply the `normalize_keys` rules to a client supplied key if enabled
+    pub(crate) fn normalize_key(&self, key: String) -> String {
+        if self.config.normalize_keys {
+            normalize_key(&key)
+        } else {
+            key
+        }
+    }
+
+    /// reject an `operation` which the access key of the request is not allowed to perform
+    pub(crate) fn validate_operation(
+        &self,
+        credentials: Option<&Credentials>,
+        operation: &str,
+    ) -> S3Result<()> {
+        if let Some(credentials) = credentials {
+            if self
+                .config
+                .operation_allowed(&credentials.access_key, operation)
+                .not()
+            {
+                return Err(s3_error!(
+                    AccessDenied,
+                    "{operation} is not allowed for this access key"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// reject a request whose `x-amz-expected-bucket-owner` is not the configured `owner_id`
+    pub(crate) fn validate_expected_bucket_owner(
+        &self,
+        expected_bucket_owner: Option<&str>,
+    ) -> S3Result<()> {
+        if let (Some(expected_bucket_owner), Some(owner_id)) =
+            (expected_bucket_owner, &self.config.owner_id)
+        {
+            if expected_bucket_owner != owner_id {
+                return Err(s3_error!(
+                    AccessDenied,
+                    "the bucket is not owned by {expected_bucket_owner}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn validate_mutable_bucket(&self, bucket: &str) -> Result<()> {
-        if self.config.read_only(Some(bucket)) {
+        if self.read_only(bucket) {
             Err(S3Error::with_message(
                 MethodNotAllowed,
                 "database is in read-only mode",
@@ -1,14 +1,22 @@
 use crate::error::{Error, Result};
 
-use s3s::StdError;
+use s3s::dto::{Range, StorageClass, Timestamp, TimestampFormat};
+use s3s::{s3_error, S3Error, S3Result, StdError};
+use std::ops::{self, Not};
 
 use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 
 use bytes::Bytes;
 use futures::{Stream, StreamExt};
+use http::header::{HeaderValue, ETAG, LAST_MODIFIED};
+use http::HeaderMap;
+use time::OffsetDateTime;
 
-pub async fn copy_bytes<S, W>(mut stream: S, writer: &mut W) -> Result<u64>
+/// copy `stream` into `writer` returning the number of bytes copied. if `max_size` is set the copy
+/// is aborted with `EntityTooLarge` as soon as more bytes are received so that a body without a
+/// `Content-Length` is never buffered beyond the limit.
+pub async fn copy_bytes<S, W>(mut stream: S, writer: &mut W, max_size: Option<u64>) -> Result<u64>
 where
     S: Stream<Item = Result<Bytes, StdError>> + Unpin,
     W: AsyncWrite + Unpin,
@@ -19,13 +27,208 @@ where
             Ok(x) => x,
             Err(e) => return Err(Error::new(e)),
         };
-        writer.write_all(&bytes).await?;
         nwritten += bytes.len() as u64;
+        if let Some(max_size) = max_size {
+            if nwritten > max_size {
+                Err(entity_too_large(max_size))?;
+            }
+        }
+        writer.write_all(&bytes).await?;
     }
     writer.flush().await?;
     Ok(nwritten)
 }
 
+/// the error for an upload larger than `max_size` bytes
+pub fn entity_too_large(max_size: u64) -> S3Error {
+    s3_error!(
+        EntityTooLarge,
+        "Your proposed upload exceeds the maximum allowed object size of {max_size} bytes."
+    )
+}
+
+/// the error for a `delete_bucket` which timed out waiting for the in-flight requests of `bucket`
+pub fn delete_bucket_timeout(bucket: &str) -> S3Error {
+    s3_error!(
+        ServiceUnavailable,
+        "the bucket {bucket} is busy, retry the deletion later"
+    )
+}
+
+/// fail with `EntityTooLarge` if a declared `Content-Length` exceeds `max_size` so that the body is
+/// not read
+pub fn check_content_length(content_length: Option<i64>, max_size: Option<u64>) -> S3Result<()> {
+    match (
+        content_length.and_then(|len| u64::try_from(len).ok()),
+        max_size,
+    ) {
+        (Some(len), Some(max_size)) if len > max_size => Err(entity_too_large(max_size)),
+        _ => Ok(()),
+    }
+}
+
+/// fail with `InvalidStorageClass` unless `storage_class` is one of the supported classes
+pub fn check_storage_class(storage_class: Option<&StorageClass>) -> S3Result<()> {
+    match storage_class {
+        Some(storage_class)
+            if ["STANDARD", "REDUCED_REDUNDANCY"]
+                .contains(&storage_class.as_str())
+                .not() =>
+        {
+            Err(s3_error!(InvalidStorageClass))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// parse a stored content type falling back to `application/octet-stream`
+pub fn parse_content_type(content_type: Option<&str>) -> mime::Mime {
+    content_type
+        .and_then(|content_type| content_type.parse().ok())
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM)
+}
+
+/// collapse repeated slashes and strip a single leading slash i.e. `//foo//bar` => `foo/bar`
+pub fn normalize_key(key: &str) -> String {
+    let mut normalized = String::with_capacity(key.len());
+    for c in key.chars() {
+        if c == '/' && normalized.ends_with('/') {
+            continue;
+        }
+        normalized.push(c);
+    }
+    match normalized.strip_prefix('/') {
+        Some(stripped) => stripped.to_string(),
+        None => normalized,
+    }
+}
+
+/// evaluate an `If-None-Match` or `If-Match` condition against the `etag` of an object. the
+/// condition is a comma-separated list of optionally quoted or weak entity tags or `*`.
+pub fn etag_matches(condition: &str, etag: &str) -> bool {
+    condition.split(',').map(str::trim).any(|candidate| {
+        candidate == "*"
+            || candidate
+                .trim_start_matches("W/")
+                .trim_matches('"')
+                .eq(etag.trim_matches('"'))
+    })
+}
+
+/// a `304 Not Modified` response carrying the `ETag` and `Last-Modified` headers of the object
+pub fn not_modified(e_tag: Option<&str>, last_modified: OffsetDateTime) -> S3Error {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = e_tag.and_then(|e_tag| HeaderValue::try_from(e_tag).ok()) {
+        headers.insert(ETAG, value);
+    }
+    let mut buf = Vec::new();
+    let formatted = Timestamp::from(last_modified).format(TimestampFormat::HttpDate, &mut buf);
+    if let (Ok(()), Ok(value)) = (formatted, HeaderValue::try_from(buf)) {
+        headers.insert(LAST_MODIFIED, value);
+    }
+
+    let mut err = s3_error!(NotModified);
+    err.set_headers(headers);
+    err
+}
+
+/// fail with a `304 Not Modified` response if `if_none_match` matches the `e_tag` of the object
+pub fn check_if_none_match(
+    if_none_match: Option<&str>,
+    e_tag: Option<&str>,
+    last_modified: OffsetDateTime,
+) -> S3Result<()> {
+    match (if_none_match, e_tag) {
+        (Some(if_none_match), Some(e_tag)) if etag_matches(if_none_match, e_tag) => {
+            Err(not_modified(Some(e_tag), last_modified))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// fail with a `304 Not Modified` response if the object has not been modified since
+/// `if_modified_since`. HTTP dates only have whole seconds so the times are compared in seconds and
+/// the condition is ignored when an `If-None-Match` header is present.
+pub fn check_if_modified_since(
+    if_none_match: Option<&str>,
+    if_modified_since: Option<OffsetDateTime>,
+    e_tag: Option<&str>,
+    last_modified: OffsetDateTime,
+) -> S3Result<()> {
+    match (if_none_match, if_modified_since) {
+        (None, Some(if_modified_since))
+            if last_modified.unix_timestamp() <= if_modified_since.unix_timestamp() =>
+        {
+            Err(not_modified(e_tag, last_modified))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// the current time truncated to whole seconds, the precision of `Last-Modified` in S3
+pub fn now_utc_seconds() -> OffsetDateTime {
+    let now = OffsetDateTime::now_utc();
+    now.replace_nanosecond(0).unwrap_or(now)
+}
+
+/// a `Content-Disposition` of `disposition` naming the file after the last segment of `key`. a
+/// name which is not printable ASCII is also given percent-encoded as UTF-8 as in RFC 6266.
+pub fn content_disposition(disposition: &str, key: &str) -> String {
+    let filename = key.rsplit('/').next().unwrap_or_default();
+    if filename.is_empty() {
+        return disposition.to_string();
+    }
+
+    let printable = |c: char| c.is_ascii_graphic() || c == ' ';
+    let fallback = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => format!("\\{c}"),
+            c if printable(c) => c.to_string(),
+            _ => "_".to_string(),
+        })
+        .collect::<String>();
+
+    let mut content_disposition = format!("{disposition}; filename=\"{fallback}\"");
+    if filename.chars().all(printable).not() {
+        content_disposition.push_str("; filename*=UTF-8''");
+        for byte in filename.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                content_disposition.push(char::from(byte));
+            } else {
+                content_disposition.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    content_disposition
+}
+
+/// the bytes of an object of `size` selected by an optional `Range` header
+pub fn object_range(range: Option<Range>, size: u64) -> S3Result<ops::Range<u64>> {
+    match range {
+        None => Ok(0..size),
+        // an empty object has no bytes to satisfy any range
+        Some(_) if size == 0 => Err(s3_error!(InvalidRange)),
+        // a suffix longer than the object returns the whole object
+        Some(Range::Suffix { length }) if length > size => Ok(0..size),
+        Some(range) => range.check(size),
+    }
+}
+
+/// percent-encode the characters of a path which have a meaning in a SQLite URI filename
+pub fn encode_uri_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '%' => encoded.push_str("%25"),
+            '?' => encoded.push_str("%3f"),
+            '#' => encoded.push_str("%23"),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
 pub fn hex(input: impl AsRef<[u8]>) -> String {
     hex_simd::encode_to_string(input, hex_simd::AsciiCase::Lower)
 }
@@ -35,6 +238,14 @@ pub fn base64(input: impl AsRef<[u8]>) -> String {
     base64.encode_to_string(input)
 }
 
+/// If a `Content-MD5` header is the padded base64 encoding of the 16 bytes of an MD5 digest
+pub fn is_valid_content_md5(content_md5: &str) -> bool {
+    let base64 = base64_simd::STANDARD;
+    base64
+        .decode_to_vec(content_md5)
+        .is_ok_and(|md5_bytes| md5_bytes.len() == 16)
+}
+
 // Helper function to return a comma-separated sequence of `?`.
 // - `repeat_vars(0) => panic!(...)`
 // - `repeat_vars(1) => "?"`
@@ -4,26 +4,53 @@
     clippy::must_use_candidate, //
 )]
 
-use s3ite::{Config, Sqlite};
+use rusqlite::config::DbConfig;
+use s3ite::{Bucket, Config, JournalMode, Pragmas, Sqlite, Synchronous, EXPIRE_OBJECTS_QUERY};
 use s3s::auth::SimpleAuth;
-use s3s::service::S3ServiceBuilder;
+use s3s::service::{S3ServiceBuilder, SharedS3Service};
+use s3s::S3Error;
 
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::ops::Deref;
+use std::ops::Not;
+use std::path::Path;
 
 use aws_config::SdkConfig;
 use aws_credential_types::provider::SharedCredentialsProvider;
 use aws_sdk_s3::config::Credentials;
 use aws_sdk_s3::config::Region;
-use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{ByteStream, DateTime};
+use aws_sdk_s3::types::BucketLifecycleConfiguration;
 use aws_sdk_s3::types::BucketLocationConstraint;
 use aws_sdk_s3::types::CompletedMultipartUpload;
 use aws_sdk_s3::types::CompletedPart;
 use aws_sdk_s3::types::CreateBucketConfiguration;
+use aws_sdk_s3::types::Delete;
+use aws_sdk_s3::types::ErrorDocument;
+use aws_sdk_s3::types::ExpirationStatus;
+use aws_sdk_s3::types::IndexDocument;
+use aws_sdk_s3::types::LifecycleExpiration;
+use aws_sdk_s3::types::LifecycleRule;
+use aws_sdk_s3::types::LifecycleRuleFilter;
+use aws_sdk_s3::types::MetadataDirective;
+use aws_sdk_s3::types::ServerSideEncryption;
+use aws_sdk_s3::types::ServerSideEncryptionByDefault;
+use aws_sdk_s3::types::ServerSideEncryptionConfiguration;
+use aws_sdk_s3::types::ServerSideEncryptionRule;
+use aws_sdk_s3::types::StorageClass;
+use aws_sdk_s3::types::WebsiteConfiguration;
 use aws_sdk_s3::Client;
 
 use anyhow::Result;
+use futures::{StreamExt, TryStreamExt};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_LENGTH, HOST, IF_NONE_MATCH};
+use hyper::service::Service;
+use hyper::{Body, Request, Response};
 use md5::{Digest, Md5};
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex;
@@ -39,7 +66,7 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     use tracing_subscriber::EnvFilter;
 
     if env::var("RUST_LOG").is_err() {
-        env::set_var("RUST_LOG", "it_aws=debug,s3s_sqlite=debug,s3s=debug");
+        env::set_var("RUST_LOG", "test=debug,s3ite=debug,s3s=debug");
     }
 
     tracing_subscriber::fmt()
@@ -51,6 +78,8 @@ static TRACING: Lazy<()> = Lazy::new(|| {
 
 pub struct TestContext {
     pub client: Client,
+    pub sqlite: Sqlite,
+    pub service: SharedS3Service,
 }
 
 impl TestContext {
@@ -64,22 +93,23 @@ impl TestContext {
         fs::create_dir_all(FS_ROOT).unwrap();
         let mut config = config.unwrap_or_default();
         config.root = FS_ROOT.into();
+        config.region = REGION.into();
 
-        let fs = Sqlite::new(&config).await.unwrap();
+        let sqlite = Sqlite::new(&config).await.unwrap();
 
         // Setup S3 service
         let service = {
-            let mut b = S3ServiceBuilder::new(fs);
+            let mut b = S3ServiceBuilder::new(sqlite.clone());
             b.set_auth(SimpleAuth::from_single(
                 cred.access_key_id(),
                 cred.secret_access_key(),
             ));
             b.set_base_domain(DOMAIN_NAME);
-            b.build()
+            b.build().into_shared()
         };
 
         // Convert to aws http connector
-        let conn = s3s_aws::Connector::from(service.into_shared());
+        let conn = s3s_aws::Connector::from(service.clone());
 
         // Setup aws sdk config
         let config = SdkConfig::builder()
@@ -91,6 +121,8 @@ impl TestContext {
 
         Self {
             client: Client::new(&config),
+            sqlite,
+            service,
         }
     }
 }
@@ -140,6 +172,120 @@ async fn delete_bucket(c: &Client, bucket: &str) -> Result<()> {
     Ok(())
 }
 
+/// create `bucket` with a service which is then dropped, leaving its files in place for a service
+/// with a bucket level configuration for it
+async fn create_bucket_file(bucket: &str) -> Result<()> {
+    let saved_path = format!("{}/{bucket}.sqlite3", env!("CARGO_TARGET_TMPDIR"));
+
+    {
+        let context = TestContext::new(None).await;
+        create_bucket(&context, bucket).await?;
+        for suffix in ["", "-wal"] {
+            fs::copy(
+                format!("{FS_ROOT}/{bucket}.sqlite3{suffix}"),
+                format!("{saved_path}{suffix}"),
+            )?;
+        }
+    }
+
+    fs::create_dir_all(FS_ROOT)?;
+    for suffix in ["", "-wal"] {
+        fs::rename(
+            format!("{saved_path}{suffix}"),
+            format!("{FS_ROOT}/{bucket}.sqlite3{suffix}"),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// the request of a presigned URL as a browser would send it, without the signing of the SDK. a
+/// `Content-Length` is only sent if the length of the body is known.
+fn presigned_request(method: &str, uri: &str, body: Body) -> Result<Request<Body>> {
+    let uri = uri.parse::<hyper::Uri>()?;
+    let host = uri.authority().map(ToString::to_string).unwrap_or_default();
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(HOST, host);
+    if let Some(content_length) = body.size_hint().exact() {
+        request = request.header(CONTENT_LENGTH, content_length);
+    }
+    Ok(request.body(body)?)
+}
+
+/// send the request of a presigned URL straight to the service
+async fn send_presigned(
+    context: &TestContext,
+    method: &str,
+    uri: &str,
+    body: Body,
+) -> Result<Response<Body>> {
+    let request = presigned_request(method, uri, body)?;
+    Ok(context.service.clone().call(request).await?)
+}
+
+/// the HTTP status code and body of the response which failed a request
+fn error_response<E>(err: &SdkError<E>) -> (Option<u16>, Vec<u8>) {
+    let response = err.raw_response();
+    (
+        response.map(|response| response.status().as_u16()),
+        response
+            .and_then(|response| response.body().bytes())
+            .unwrap_or_default()
+            .to_vec(),
+    )
+}
+
+/// run `query` against every connection the pool of `bucket` can open by checking them all out at
+/// once, returning the result from each connection
+async fn query_pool_connections<T, F>(
+    context: &TestContext,
+    bucket: &str,
+    query: F,
+) -> Result<Vec<T>>
+where
+    F: Fn(&rusqlite::Transaction) -> rusqlite::Result<T> + Clone + Send + 'static,
+    T: Send + 'static,
+{
+    let max_size = context
+        .sqlite
+        .pool_stats(bucket)
+        .await
+        .map_err(S3Error::from)?
+        .max_size;
+
+    let handles = (0..max_size)
+        .map(|_| {
+            let sqlite = context.sqlite.clone();
+            let bucket = bucket.to_string();
+            let query = query.clone();
+            tokio::spawn(async move {
+                sqlite
+                    .with_reader(&bucket, move |transaction| {
+                        std::thread::sleep(std::time::Duration::from_millis(200));
+                        query(transaction)
+                    })
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::new();
+    for handle in handles {
+        results.push(handle.await?.map_err(S3Error::from)?);
+    }
+
+    let pool_stats = context
+        .sqlite
+        .pool_stats(bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert!(pool_stats.size > 1);
+
+    Ok(results)
+}
+
 pub fn base64(input: impl AsRef<[u8]>) -> String {
     let base64 = base64_simd::STANDARD;
     base64.encode_to_string(input)
@@ -186,10 +332,66 @@ async fn test_list_buckets() -> Result<()> {
 
 #[tokio::test]
 #[tracing::instrument]
-async fn test_single_object() -> Result<()> {
+async fn test_not_found() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-not-found-{}", Uuid::new_v4());
+    let missing_bucket = format!("test-not-found-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    for (bucket, code) in [(&missing_bucket, "NoSuchBucket"), (&bucket, "NoSuchKey")] {
+        match context.get_object().bucket(bucket).key(key).send().await {
+            Err(err) if format!("{err:?}").contains(code) => {
+                assert_eq!(error_response(&err).0, Some(404));
+            }
+            other => panic!("{:?}", other),
+        };
+
+        // a HEAD response has no body to carry the error code
+        match context.head_object().bucket(bucket).key(key).send().await {
+            Err(err) => assert_eq!(error_response(&err), (Some(404), Vec::new())),
+            other => panic!("{:?}", other),
+        };
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_bucket() -> Result<()> {
     let _guard = serial().await;
     let context = TestContext::new(None).await;
 
+    let bucket = format!("test-head-bucket-{}", Uuid::new_v4());
+    let missing_bucket = format!("test-head-bucket-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    context.head_bucket().bucket(&bucket).send().await?;
+
+    match context.head_bucket().bucket(&missing_bucket).send().await {
+        Err(err) => assert_eq!(error_response(&err), (Some(404), Vec::new())),
+        other => panic!("{:?}", other),
+    };
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_single_object() -> Result<()> {
+    let _guard = serial().await;
+    // every read asserts that the value still matches the stored ETag
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
     let bucket = format!("test-single-object-{}", Uuid::new_v4());
     let key = "sample.txt";
     let content = "hello world\n你好世界\n";
@@ -231,7 +433,11 @@ async fn test_single_object() -> Result<()> {
 #[tracing::instrument]
 async fn test_multipart() -> Result<()> {
     let _guard = serial().await;
-    let context = TestContext::new(None).await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
 
     let bucket = format!("test-multipart-{}", Uuid::new_v4());
     create_bucket(&context, &bucket).await?;
@@ -348,3 +554,4850 @@ async fn test_read_only_object() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_object_cache() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        object_cache_capacity: 16,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-object-cache-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    for content in ["hello world", "goodbye world"] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(content.as_bytes()))
+            .send()
+            .await?;
+
+        // the first read populates the cache
+        let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+
+        // change the object behind the cache so that only a cached read returns the original value
+        let changed = content.to_uppercase();
+        let connection = rusqlite::Connection::open(format!("{FS_ROOT}/{bucket}.sqlite3"))?;
+        connection.execute(
+            "UPDATE data SET value = ?2 WHERE key = ?1;",
+            (key, changed.as_bytes()),
+        )?;
+        connection.execute(
+            "UPDATE metadata SET md5 = ?2 WHERE key = ?1;",
+            (key, format!("{:x}", Md5::digest(changed.as_bytes()))),
+        )?;
+        drop(connection);
+
+        let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+    }
+
+    // a put invalidates the cached object so the next read sees the new value
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello again"))
+        .send()
+        .await?;
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello again");
+
+    {
+        delete_object(&context, &bucket, key).await?;
+
+        let result = context.get_object().bucket(&bucket).key(key).send().await;
+        assert!(result.is_err());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_read_your_writes() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-read-your-writes-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    for i in 0..1000 {
+        let content = format!("content-{i}");
+
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from(content.clone().into_bytes()))
+            .send()
+            .await?;
+
+        let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_idempotent_create_bucket() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-idempotent-create-bucket-{}", Uuid::new_v4());
+
+    {
+        let context = TestContext::new(None).await;
+        create_bucket(&context, &bucket).await?;
+        assert!(create_bucket(&context, &bucket).await.is_err());
+    }
+
+    {
+        let context = TestContext::new(Some(Config {
+            idempotent_create_bucket: true,
+            ..Default::default()
+        }))
+        .await;
+        create_bucket(&context, &bucket).await?;
+        create_bucket(&context, &bucket).await?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_max_buckets() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-max-buckets-{}", Uuid::new_v4());
+    let rejected = format!("test-max-buckets-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    // the root is shared with the other tests so the limit allows exactly one more bucket
+    let existing = {
+        let context = TestContext::new(None).await;
+        context
+            .list_buckets()
+            .send()
+            .await?
+            .buckets
+            .unwrap_or_default()
+            .len()
+    };
+
+    let context = TestContext::new(Some(Config {
+        max_buckets: Some(existing + 1),
+        ..Default::default()
+    }))
+    .await;
+
+    create_bucket(&context, &bucket).await?;
+
+    match create_bucket(&context, &rejected).await {
+        Err(err) if format!("{err:?}").contains("TooManyBuckets") => {}
+        other => panic!("{:?}", other),
+    }
+    assert!(Path::new(&format!("{FS_ROOT}/{rejected}.sqlite3"))
+        .exists()
+        .not());
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    context.get_object().bucket(&bucket).key(key).send().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_multipart_metadata() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-multipart-metadata-{}", Uuid::new_v4());
+    create_bucket(&context, &bucket).await?;
+
+    let key = "sample.txt";
+    let content = "abcdefghijklmnopqrstuvwxyz/0123456789/!@#$%^&*();\n";
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .content_type("text/plain")
+        .metadata("foo", "bar")
+        .storage_class(StorageClass::ReducedRedundancy)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    let e_tag = context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .part_number(1)
+        .send()
+        .await?
+        .e_tag
+        .unwrap_or_default();
+
+    let upload = CompletedMultipartUpload::builder()
+        .parts(CompletedPart::builder().e_tag(e_tag).part_number(1).build())
+        .build();
+
+    context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(upload)
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(head_object_output.content_type(), Some("text/plain"));
+    assert_eq!(
+        head_object_output
+            .metadata()
+            .and_then(|metadata| metadata.get("foo"))
+            .map(String::as_str),
+        Some("bar")
+    );
+    assert_eq!(
+        head_object_output.storage_class(),
+        Some(&StorageClass::ReducedRedundancy)
+    );
+
+    // an unsupported storage class is rejected when the upload is created
+    match context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .storage_class(StorageClass::Glacier)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidStorageClass") => {}
+        other => panic!("{:?}", other),
+    };
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_multipart_restart() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-multipart-restart-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let parts = ["first part/", "second part"];
+
+    let (upload_id, e_tag) = {
+        let context = TestContext::new(None).await;
+        create_bucket(&context, &bucket).await?;
+
+        let upload_id = context
+            .create_multipart_upload()
+            .bucket(&bucket)
+            .key(key)
+            .content_type("text/plain")
+            .metadata("foo", "bar")
+            .send()
+            .await?
+            .upload_id
+            .unwrap();
+
+        let e_tag = context
+            .upload_part()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .body(ByteStream::from_static(parts[0].as_bytes()))
+            .part_number(1)
+            .send()
+            .await?
+            .e_tag
+            .unwrap_or_default();
+
+        (upload_id, e_tag)
+    };
+
+    // the upload is held in the bucket database only so it can be resumed by a new service
+    let context = TestContext::new(None).await;
+
+    let list_parts_output = context
+        .list_parts()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+    assert_eq!(list_parts_output.parts().unwrap_or_default().len(), 1);
+
+    let second_e_tag = context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(parts[1].as_bytes()))
+        .part_number(2)
+        .send()
+        .await?
+        .e_tag
+        .unwrap_or_default();
+
+    let upload = CompletedMultipartUpload::builder()
+        .parts(CompletedPart::builder().e_tag(e_tag).part_number(1).build())
+        .parts(
+            CompletedPart::builder()
+                .e_tag(second_e_tag)
+                .part_number(2)
+                .build(),
+        )
+        .build();
+
+    context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(upload)
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    assert_eq!(get_object_output.content_type(), Some("text/plain"));
+    assert_eq!(
+        get_object_output
+            .metadata()
+            .and_then(|metadata| metadata.get("foo"))
+            .map(String::as_str),
+        Some("bar")
+    );
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), parts.concat().as_bytes());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_import_mode_last_modified() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        import_mode: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-import-mode-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .metadata("s3ite-last-modified", "2020-01-02T03:04:05Z")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        head_object_output
+            .last_modified()
+            .map(|last_modified| last_modified.secs()),
+        Some(1_577_934_245)
+    );
+    assert!(!head_object_output
+        .metadata()
+        .is_some_and(|metadata| metadata.contains_key("s3ite-last-modified")));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_by_metadata() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-objects-by-metadata-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, status) in [
+        ("logs/a.txt", Some("archived")),
+        ("logs/b.txt", Some("active")),
+        ("logs/c.txt", None),
+        ("other.txt", Some("archived")),
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .set_metadata(
+                status.map(|status| HashMap::from([("status".to_string(), status.to_string())])),
+            )
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let key_sizes = context
+        .sqlite
+        .list_objects_by_metadata(&bucket, "status", "archived", None, 10)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        key_sizes
+            .iter()
+            .map(|key_size| key_size.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["logs/a.txt", "other.txt"]
+    );
+
+    let key_sizes = context
+        .sqlite
+        .list_objects_by_metadata(&bucket, "status", "archived", Some("logs/".to_string()), 10)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        key_sizes
+            .iter()
+            .map(|key_size| key_size.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["logs/a.txt"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_by_last_modified() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        import_mode: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-list-objects-by-last-modified-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, last_modified) in [
+        ("logs/a.txt", "2020-01-01T00:00:00Z"),
+        ("logs/b.txt", "2022-01-01T00:00:00Z"),
+        ("logs/c.txt", "2021-01-01T00:00:00Z"),
+        ("other.txt", "2023-01-01T00:00:00Z"),
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .metadata("s3ite-last-modified", last_modified)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let key_sizes = context
+        .sqlite
+        .list_objects_by_last_modified(&bucket, Some("logs/".to_string()), 10)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        key_sizes
+            .iter()
+            .map(|key_size| key_size.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["logs/b.txt", "logs/c.txt", "logs/a.txt"]
+    );
+
+    let key_sizes = context
+        .sqlite
+        .list_objects_by_last_modified(&bucket, None, 2)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        key_sizes
+            .iter()
+            .map(|key_size| key_size.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["other.txt", "logs/b.txt"]
+    );
+
+    // the S3 listing remains in key order
+    let list_objects_output = context.list_objects_v2().bucket(&bucket).send().await?;
+    assert_eq!(
+        list_objects_output
+            .contents()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|object| object.key())
+            .collect::<Vec<_>>(),
+        vec!["logs/a.txt", "logs/b.txt", "logs/c.txt", "other.txt"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_get_object_range() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-get-object-range-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "0123456789";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    for (range, expected) in [
+        ("bytes=2-4", "234"),
+        ("bytes=7-", "789"),
+        ("bytes=-3", "789"),
+        ("bytes=-1000000", content),
+    ] {
+        let get_object_output = context
+            .get_object()
+            .bucket(&bucket)
+            .key(key)
+            .range(range)
+            .send()
+            .await?;
+        let content_length: usize = get_object_output.content_length().try_into().unwrap();
+        let body = get_object_output.body.collect().await?.into_bytes();
+
+        assert_eq!(content_length, expected.len());
+        assert_eq!(body.as_ref(), expected.as_bytes());
+    }
+
+    // no range can be satisfied by an empty object
+    let empty_key = "empty.txt";
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(empty_key)
+        .body(ByteStream::from_static(b""))
+        .send()
+        .await?;
+    for range in ["bytes=-1000000", "bytes=0-"] {
+        match context
+            .get_object()
+            .bucket(&bucket)
+            .key(empty_key)
+            .range(range)
+            .send()
+            .await
+        {
+            Err(err) if format!("{err:?}").contains("InvalidRange") => {
+                assert_eq!(error_response(&err).0, Some(416));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_object_accept_ranges() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-head-object-accept-ranges-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "0123456789";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    // a range on a `head_object` still reports the full length
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .range("bytes=2-4")
+        .send()
+        .await?;
+    assert_eq!(head_object_output.accept_ranges(), Some("bytes"));
+    assert_eq!(head_object_output.content_length(), 10);
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .range("bytes=2-4")
+        .send()
+        .await?;
+    assert_eq!(get_object_output.accept_ranges(), Some("bytes"));
+    assert_eq!(get_object_output.content_length(), 3);
+    assert_eq!(get_object_output.content_range(), Some("bytes 2-4/10"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_get_object_range_blob() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        blob_threshold: Some(0),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-get-object-range-blob-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "0123456789";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    // only the requested bytes are read from the blob file
+    for (range, expected) in [("bytes=2-4", "234"), ("bytes=-3", "789")] {
+        let get_object_output = context
+            .get_object()
+            .bucket(&bucket)
+            .key(key)
+            .range(range)
+            .send()
+            .await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+
+        assert_eq!(body.as_ref(), expected.as_bytes());
+    }
+
+    assert!(context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .range("bytes=20-30")
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_to_itself() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-copy-object-to-itself-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let result = context
+        .copy_object()
+        .bucket(&bucket)
+        .key(key)
+        .copy_source(format!("{bucket}/{key}"))
+        .send()
+        .await;
+    assert!(result.is_err());
+
+    context
+        .copy_object()
+        .bucket(&bucket)
+        .key(key)
+        .copy_source(format!("{bucket}/{key}"))
+        .metadata_directive(MetadataDirective::Replace)
+        .metadata("foo", "bar")
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_replace_empty_metadata() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-copy-object-replace-empty-metadata-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .metadata("foo", "bar")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    // a self-copy with `REPLACE` and no metadata strips the metadata
+    context
+        .copy_object()
+        .bucket(&bucket)
+        .key(key)
+        .copy_source(format!("{bucket}/{key}"))
+        .metadata_directive(MetadataDirective::Replace)
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert!(head_object_output
+        .metadata()
+        .map_or(true, |metadata| metadata.is_empty()));
+
+    let body = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_if_none_match() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-copy-object-if-none-match-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, content) in [("source.txt", "source"), ("existing.txt", "existing")] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(content.as_bytes()))
+            .send()
+            .await?;
+    }
+
+    let copy_if_none_match = |key: &'static str| {
+        context
+            .copy_object()
+            .bucket(&bucket)
+            .key(key)
+            .copy_source(format!("{bucket}/source.txt"))
+            .customize()
+    };
+
+    // an existing target is not overwritten
+    match copy_if_none_match("existing.txt")
+        .await?
+        .mutate_request(|request| {
+            request
+                .headers_mut()
+                .insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+        })
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("PreconditionFailed") => {
+            assert_eq!(error_response(&err).0, Some(412));
+        }
+        other => panic!("{:?}", other),
+    };
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key("existing.txt")
+        .send()
+        .await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"existing");
+
+    // a missing target is created
+    copy_if_none_match("created.txt")
+        .await?
+        .mutate_request(|request| {
+            request
+                .headers_mut()
+                .insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+        })
+        .send()
+        .await?;
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key("created.txt")
+        .send()
+        .await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"source");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_copy_object_content_type() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-copy-object-content-type-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .content_type("text/plain")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    for (target, metadata_directive, content_type, expected) in [
+        ("copy.txt", None, None, "text/plain"),
+        (
+            "replace.txt",
+            Some(MetadataDirective::Replace),
+            None,
+            "application/octet-stream",
+        ),
+        (
+            "replace.html",
+            Some(MetadataDirective::Replace),
+            Some("text/html"),
+            "text/html",
+        ),
+    ] {
+        context
+            .copy_object()
+            .bucket(&bucket)
+            .key(target)
+            .copy_source(format!("{bucket}/{key}"))
+            .set_metadata_directive(metadata_directive)
+            .set_content_type(content_type.map(ToString::to_string))
+            .send()
+            .await?;
+
+        let head_object_output = context
+            .head_object()
+            .bucket(&bucket)
+            .key(target)
+            .send()
+            .await?;
+        assert_eq!(head_object_output.content_type(), Some(expected));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_set_bucket_read_only() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-set-bucket-read-only-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let multipart_key = "multipart.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    let create_multipart_upload_output = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(multipart_key)
+        .send()
+        .await?;
+    let upload_id = create_multipart_upload_output.upload_id().unwrap();
+
+    // open several connections so that the pool holds connections from before the override
+    query_pool_connections(&context, &bucket, |_| Ok(())).await?;
+
+    context
+        .sqlite
+        .set_bucket_read_only(&bucket, true)
+        .await
+        .map_err(S3Error::from)?;
+
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await
+        .map_err(anyhow::Error::from)
+    {
+        Err(err)
+            if err
+                .root_cause()
+                .to_string()
+                .contains("database is in read-only mode") => {}
+        other => panic!("{:?}", other),
+    };
+
+    match context
+        .delete_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(anyhow::Error::from)
+    {
+        Err(err)
+            if err
+                .root_cause()
+                .to_string()
+                .contains("database is in read-only mode") => {}
+        other => panic!("{:?}", other),
+    };
+
+    match context
+        .upload_part()
+        .bucket(&bucket)
+        .key(multipart_key)
+        .upload_id(upload_id)
+        .part_number(1)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await
+        .map_err(anyhow::Error::from)
+    {
+        Err(err)
+            if err
+                .root_cause()
+                .to_string()
+                .contains("database is in read-only mode") => {}
+        other => panic!("{:?}", other),
+    };
+
+    // the connections of the pool are `query_only` as well
+    let query_only = context
+        .sqlite
+        .with_bucket_quiesced(&bucket, |connection| {
+            connection.query_row("PRAGMA query_only;", [], |row| row.get::<_, bool>(0))
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert!(query_only);
+
+    context
+        .sqlite
+        .set_bucket_read_only(&bucket, false)
+        .await
+        .map_err(S3Error::from)?;
+
+    let query_only = context
+        .sqlite
+        .with_bucket_quiesced(&bucket, |connection| {
+            connection.query_row("PRAGMA query_only;", [], |row| row.get::<_, bool>(0))
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert!(query_only.not());
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    delete_object(&context, &bucket, key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_bucket_not_empty() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-delete-bucket-not-empty-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    match delete_bucket(&context, &bucket).await {
+        Err(err) if format!("{err:?}").contains("BucketNotEmpty") => {}
+        other => panic!("{:?}", other),
+    };
+
+    // the bucket is still usable
+    context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+
+    delete_object(&context, &bucket, key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_bucket_concurrent_requests() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let src_bucket = format!("test-delete-bucket-concurrent-src-{}", Uuid::new_v4());
+    let tgt_bucket = format!("test-delete-bucket-concurrent-tgt-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &src_bucket).await?;
+    create_bucket(&context, &tgt_bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&src_bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    // copies out of and writes into the bucket while it is being deleted must neither deadlock
+    // the deletion nor the other buckets
+    let handles = (0..32)
+        .map(|i| {
+            let client = context.client.clone();
+            let src_bucket = src_bucket.clone();
+            let tgt_bucket = tgt_bucket.clone();
+            tokio::spawn(async move {
+                client
+                    .copy_object()
+                    .bucket(&tgt_bucket)
+                    .key(format!("copy-{i}.txt"))
+                    .copy_source(format!("{src_bucket}/{key}"))
+                    .send()
+                    .await?;
+                client
+                    .put_object()
+                    .bucket(&src_bucket)
+                    .key(format!("put-{i}.txt"))
+                    .body(ByteStream::from_static(b"hello world"))
+                    .send()
+                    .await?;
+                Ok::<_, anyhow::Error>(())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let result = tokio::time::timeout(
+        std::time::Duration::from_secs(10),
+        delete_bucket(&context, &src_bucket),
+    )
+    .await?;
+    match result {
+        Err(err) if format!("{err:?}").contains("BucketNotEmpty") => {}
+        other => panic!("{:?}", other),
+    };
+
+    for handle in handles {
+        tokio::time::timeout(std::time::Duration::from_secs(10), handle).await???;
+    }
+
+    context
+        .head_object()
+        .bucket(&tgt_bucket)
+        .key("copy-0.txt")
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_force_delete_bucket() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        force_delete_bucket: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-force-delete-bucket-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("sample.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    delete_bucket(&context, &bucket).await?;
+
+    assert!(context.head_bucket().bucket(&bucket).send().await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_bucket_failed_removal() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        sqlite: Pragmas {
+            journal_mode: JournalMode::DELETE,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-delete-bucket-failed-removal-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    // a directory in place of the `-wal` sidecar cannot be removed as a file
+    let wal_path = format!("{FS_ROOT}/{bucket}.sqlite3-wal");
+    fs::create_dir(&wal_path)?;
+
+    assert!(delete_bucket(&context, &bucket).await.is_err());
+
+    // the bucket is still listed and usable
+    let list_buckets = context.list_buckets().send().await?;
+    assert!(list_buckets
+        .buckets()
+        .unwrap()
+        .iter()
+        .any(|list_bucket| list_bucket.name() == Some(bucket.as_str())));
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    delete_object(&context, &bucket, key).await?;
+
+    fs::remove_dir(&wal_path)?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_normalize_keys() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        normalize_keys: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-normalize-keys-{}", Uuid::new_v4());
+    let content = "hello world";
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, normalized) in [
+        ("/foo/bar.txt", "foo/bar.txt"),
+        ("foo//bar.txt", "foo/bar.txt"),
+        ("//foo///bar.txt", "foo/bar.txt"),
+        ("foo/bar.txt", "foo/bar.txt"),
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(content.as_bytes()))
+            .send()
+            .await?;
+
+        let get_object_output = context
+            .get_object()
+            .bucket(&bucket)
+            .key(normalized)
+            .send()
+            .await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+
+        let list_objects_output = context
+            .list_objects_v2()
+            .bucket(&bucket)
+            .prefix("/foo//")
+            .send()
+            .await?;
+        let keys = list_objects_output
+            .contents()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|object| object.key())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![normalized]);
+
+        delete_object(&context, &bucket, key).await?;
+    }
+
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_rename_object() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        blob_threshold: Some(0),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-rename-object-{}", Uuid::new_v4());
+    let src_key = "source.txt";
+    let tgt_key = "target.txt";
+    let content = "hello world";
+    let blob_dir = format!("{FS_ROOT}/{bucket}.blobs");
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, body) in [(src_key, content), (tgt_key, "stale")] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(body.as_bytes()))
+            .send()
+            .await?;
+    }
+
+    // the target exists so the rename is rejected unless overwriting
+    assert!(context
+        .sqlite
+        .rename_object(&bucket, src_key, tgt_key, false)
+        .await
+        .is_err());
+    context
+        .sqlite
+        .rename_object(&bucket, src_key, tgt_key, true)
+        .await
+        .map_err(S3Error::from)?;
+
+    // the file of the overwritten target is left for the maintenance process
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 2);
+
+    assert!(context
+        .head_object()
+        .bucket(&bucket)
+        .key(src_key)
+        .send()
+        .await
+        .is_err());
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key(tgt_key)
+        .send()
+        .await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    delete_object(&context, &bucket, tgt_key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_iter_objects() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-iter-objects-{}", Uuid::new_v4());
+    let keys = ["a/1.txt", "a/2.txt", "b/1.txt"];
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in keys {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let key_sizes = context
+        .sqlite
+        .iter_objects(&bucket, Some("a/".to_string()))
+        .try_collect::<Vec<_>>()
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        key_sizes
+            .iter()
+            .map(|key_size| key_size.key.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a/1.txt", "a/2.txt"]
+    );
+    assert!(key_sizes.iter().all(|key_size| key_size.size == 11));
+
+    for key in keys {
+        delete_object(&context, &bucket, key).await?;
+    }
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_lifecycle_expiration() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-lifecycle-expiration-{}", Uuid::new_v4());
+    let expired_key = "tmp/sample.txt";
+    let retained_key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in [expired_key, retained_key] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let rule = LifecycleRule::builder()
+        .id("expire-tmp")
+        .filter(LifecycleRuleFilter::Prefix("tmp/".to_string()))
+        .expiration(LifecycleExpiration::builder().days(0).build())
+        .status(ExpirationStatus::Enabled)
+        .build();
+    context
+        .put_bucket_lifecycle_configuration()
+        .bucket(&bucket)
+        .lifecycle_configuration(BucketLifecycleConfiguration::builder().rules(rule).build())
+        .send()
+        .await?;
+
+    let get_bucket_lifecycle_configuration_output = context
+        .get_bucket_lifecycle_configuration()
+        .bucket(&bucket)
+        .send()
+        .await?;
+    let rules = get_bucket_lifecycle_configuration_output.rules().unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(rules[0].id(), Some("expire-tmp"));
+
+    // wait for the next maintenance sweep
+    tokio::time::sleep(std::time::Duration::from_millis(11000)).await;
+
+    assert!(context
+        .head_object()
+        .bucket(&bucket)
+        .key(expired_key)
+        .send()
+        .await
+        .is_err());
+    context
+        .head_object()
+        .bucket(&bucket)
+        .key(retained_key)
+        .send()
+        .await?;
+
+    context
+        .delete_bucket_lifecycle()
+        .bucket(&bucket)
+        .send()
+        .await?;
+    assert!(context
+        .get_bucket_lifecycle_configuration()
+        .bucket(&bucket)
+        .send()
+        .await
+        .is_err());
+
+    delete_object(&context, &bucket, retained_key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_lifecycle_expiration_literal_prefix() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!(
+        "test-lifecycle-expiration-literal-prefix-{}",
+        Uuid::new_v4()
+    );
+    let expired_key = "logs_a/sample.txt";
+    // the prefix is matched literally so neither a different case nor `_` as a wildcard matches
+    let retained_keys = ["LOGS_A/sample.txt", "logsXa/sample.txt"];
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in retained_keys.iter().chain([&expired_key]) {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(*key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let rule = LifecycleRule::builder()
+        .id("expire-logs")
+        .filter(LifecycleRuleFilter::Prefix("logs_a/".to_string()))
+        .expiration(LifecycleExpiration::builder().days(0).build())
+        .status(ExpirationStatus::Enabled)
+        .build();
+    context
+        .put_bucket_lifecycle_configuration()
+        .bucket(&bucket)
+        .lifecycle_configuration(BucketLifecycleConfiguration::builder().rules(rule).build())
+        .send()
+        .await?;
+
+    // the last modified time is stored in whole seconds
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    context.sqlite.run_maintenance().await;
+
+    assert!(context
+        .head_object()
+        .bucket(&bucket)
+        .key(expired_key)
+        .send()
+        .await
+        .is_err());
+    for key in retained_keys {
+        context
+            .head_object()
+            .bucket(&bucket)
+            .key(key)
+            .send()
+            .await?;
+        delete_object(&context, &bucket, key).await?;
+    }
+
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_object_expires_at() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-object-expires-at-{}", Uuid::new_v4());
+    let expired_key = "expired.txt";
+    let retained_key = "retained.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    for (key, expires_at) in [
+        (expired_key, "2020-01-01T00:00:00Z"),
+        (retained_key, "2999-01-01T00:00:00Z"),
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .metadata("s3ite-expires-at", expires_at)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    match context
+        .get_object()
+        .bucket(&bucket)
+        .key(expired_key)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("NoSuchKey") => {}
+        other => panic!("{:?}", other),
+    };
+    context
+        .get_object()
+        .bucket(&bucket)
+        .key(retained_key)
+        .send()
+        .await?;
+
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key(retained_key)
+        .metadata("s3ite-expires-at", "tomorrow")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+        other => panic!("{:?}", other),
+    };
+
+    // wait for the next maintenance sweep
+    tokio::time::sleep(std::time::Duration::from_millis(11000)).await;
+
+    let keys = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            let mut stmt = transaction.prepare("SELECT key FROM metadata ORDER BY key;")?;
+            let keys = stmt
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(keys)
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(keys, vec![retained_key]);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_run_maintenance() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-run-maintenance-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("sample.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    // no checkpoint runs after the interval of the background maintenance process
+    tokio::time::sleep(std::time::Duration::from_millis(11000)).await;
+    assert!(context
+        .sqlite
+        .wal_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?
+        .is_none());
+
+    context.sqlite.run_maintenance().await;
+    assert!(context
+        .sqlite
+        .wal_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?
+        .is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_last_modified_index() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-last-modified-index-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    // the lifecycle sweep query
+    let query_plan = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            let mut stmt =
+                transaction.prepare(&format!("EXPLAIN QUERY PLAN {EXPIRE_OBJECTS_QUERY}"))?;
+            let details = stmt
+                .query_map(("tmp/", "2020-01-01 00:00:00.0+00:00"), |row| {
+                    row.get::<_, String>(3)
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(details.join("\n"))
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert!(
+        query_plan.contains("USING INDEX metadata_last_modified"),
+        "{query_plan}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_common_prefixes() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-objects-common-prefixes-{}", Uuid::new_v4());
+    let keys = ["a/1.txt", "a/2.txt", "b/1.txt", "c.txt", "d.txt"];
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in keys {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let list_objects_output = context
+        .list_objects_v2()
+        .bucket(&bucket)
+        .delimiter("/")
+        .send()
+        .await?;
+
+    let contents = list_objects_output
+        .contents()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|object| object.key())
+        .collect::<Vec<_>>();
+    let common_prefixes = list_objects_output
+        .common_prefixes()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|common_prefix| common_prefix.prefix())
+        .collect::<Vec<_>>();
+
+    assert_eq!(contents, vec!["c.txt", "d.txt"]);
+    assert_eq!(common_prefixes, vec!["a/", "b/"]);
+    assert_eq!(list_objects_output.key_count(), 4);
+
+    for key in keys {
+        delete_object(&context, &bucket, key).await?;
+    }
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_prefix_edge_cases() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-objects-prefix-edge-cases-{}", Uuid::new_v4());
+    let keys = ["A/3.txt", "a", "a/1.txt", "a/b/2.txt", "a_b.txt", "axb.txt"];
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in keys {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    for (prefix, delimiter, expected_contents, expected_common_prefixes) in [
+        // an empty prefix lists every key
+        ("", None, keys.to_vec(), vec![]),
+        // a prefix equal to a key includes that key and is case sensitive
+        (
+            "a",
+            None,
+            vec!["a", "a/1.txt", "a/b/2.txt", "a_b.txt", "axb.txt"],
+            vec![],
+        ),
+        // a prefix ending in the delimiter lists the immediate children of the folder
+        ("a/", Some("/"), vec!["a/1.txt"], vec!["a/b/"]),
+        // wildcard characters in a prefix match only themselves
+        ("a_", None, vec!["a_b.txt"], vec![]),
+    ] {
+        let list_objects_output = context
+            .list_objects_v2()
+            .bucket(&bucket)
+            .prefix(prefix)
+            .set_delimiter(delimiter.map(str::to_string))
+            .send()
+            .await?;
+
+        let contents = list_objects_output
+            .contents()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|object| object.key())
+            .collect::<Vec<_>>();
+        let common_prefixes = list_objects_output
+            .common_prefixes()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|common_prefix| common_prefix.prefix())
+            .collect::<Vec<_>>();
+
+        assert_eq!(contents, expected_contents);
+        assert_eq!(common_prefixes, expected_common_prefixes);
+        if prefix.is_empty().not() {
+            assert_eq!(list_objects_output.prefix(), Some(prefix));
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_max_parts() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-max-parts-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    for part_number in [0, 10001] {
+        match context
+            .upload_part()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .body(ByteStream::from_static(b"hello world"))
+            .part_number(part_number)
+            .send()
+            .await
+        {
+            Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+            other => panic!("{:?}", other),
+        };
+    }
+
+    context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(b"hello world"))
+        .part_number(10000)
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_max_parts_configured() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        max_parts: 2,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-max-parts-configured-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    match context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .body(ByteStream::from_static(b"hello world"))
+        .part_number(3)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+        other => panic!("{:?}", other),
+    };
+
+    let mut upload = CompletedMultipartUpload::builder();
+    for part_number in 1..=3 {
+        upload = upload.parts(CompletedPart::builder().part_number(part_number).build());
+    }
+
+    match context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(upload.build())
+        .upload_id(&upload_id)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+        other => panic!("{:?}", other),
+    };
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_content_md5() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-content-md5-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "hello world";
+
+    create_bucket(&context, &bucket).await?;
+
+    let mut md5_hash = Md5::new();
+    md5_hash.update(content);
+    let md5_bytes = md5_hash.finalize();
+    let hex_md5 = md5_bytes
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    // a hex digest is malformed, a well-formed digest of other content does not match
+    for (content_md5, code) in [
+        (hex_md5.as_str(), "InvalidDigest"),
+        ("not base64", "InvalidDigest"),
+        ("1B2M2Y8AsgTpgAmY7PhCfg==", "BadDigest"),
+    ] {
+        match context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .content_md5(content_md5)
+            .body(ByteStream::from_static(content.as_bytes()))
+            .send()
+            .await
+        {
+            Err(err) if format!("{err:?}").contains(code) => {}
+            other => panic!("{:?}", other),
+        };
+
+        match context
+            .upload_part()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .part_number(1)
+            .content_md5(content_md5)
+            .body(ByteStream::from_static(content.as_bytes()))
+            .send()
+            .await
+        {
+            Err(err) if format!("{err:?}").contains(code) => {}
+            other => panic!("{:?}", other),
+        };
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_require_part_md5() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        require_part_md5: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-require-part-md5-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "hello world";
+
+    create_bucket(&context, &bucket).await?;
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    match context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .part_number(1)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidRequest") => {}
+        other => panic!("{:?}", other),
+    };
+
+    context
+        .upload_part()
+        .bucket(&bucket)
+        .key(key)
+        .upload_id(&upload_id)
+        .part_number(1)
+        .content_md5(base64(Md5::digest(content)))
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_parts_pagination() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-parts-pagination-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    for part_number in 1..=20 {
+        context
+            .upload_part()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .body(ByteStream::from(part_number.to_string().into_bytes()))
+            .part_number(part_number)
+            .send()
+            .await?;
+    }
+
+    let mut part_numbers = Vec::new();
+    let mut part_number_marker = None;
+    let mut pages = 0;
+    loop {
+        let list_parts_output = context
+            .list_parts()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .max_parts(5)
+            .set_part_number_marker(part_number_marker)
+            .send()
+            .await?;
+        pages += 1;
+
+        let parts = list_parts_output.parts().unwrap_or_default();
+        assert_eq!(parts.len(), 5);
+        part_numbers.extend(parts.iter().map(|part| part.part_number()));
+
+        if list_parts_output.is_truncated().not() {
+            assert!(list_parts_output.next_part_number_marker().is_none());
+            break;
+        }
+        part_number_marker = list_parts_output
+            .next_part_number_marker()
+            .map(ToString::to_string);
+    }
+
+    assert_eq!(pages, 4);
+    assert_eq!(part_numbers, (1..=20).collect::<Vec<_>>());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_multipart_part_numbers() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-multipart-part-numbers-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    let upload_id = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .upload_id
+        .unwrap();
+
+    for (part_number, body) in [(1, "one"), (2, "two"), (3, "three"), (5, "five")] {
+        context
+            .upload_part()
+            .bucket(&bucket)
+            .key(key)
+            .upload_id(&upload_id)
+            .body(ByteStream::from_static(body.as_bytes()))
+            .part_number(part_number)
+            .send()
+            .await?;
+    }
+
+    let completed_multipart_upload = |part_numbers: &[i32]| {
+        CompletedMultipartUpload::builder()
+            .set_parts(Some(
+                part_numbers
+                    .iter()
+                    .map(|part_number| CompletedPart::builder().part_number(*part_number).build())
+                    .collect(),
+            ))
+            .build()
+    };
+
+    // duplicate part numbers are rejected
+    assert!(context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(completed_multipart_upload(&[1, 1]))
+        .upload_id(&upload_id)
+        .send()
+        .await
+        .is_err());
+
+    // part numbers out of order are rejected
+    assert!(context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(completed_multipart_upload(&[3, 1]))
+        .upload_id(&upload_id)
+        .send()
+        .await
+        .is_err());
+
+    // gaps are allowed and unlisted parts are discarded
+    context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(key)
+        .multipart_upload(completed_multipart_upload(&[1, 3, 5]))
+        .upload_id(&upload_id)
+        .send()
+        .await?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"onethreefive");
+
+    delete_object(&context, &bucket, key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_blob_threshold() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        blob_threshold: Some(16),
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-blob-threshold-{}", Uuid::new_v4());
+    let small_key = "small.txt";
+    let large_key = "large.txt";
+    let large_content = "hello world ".repeat(64);
+    let blob_dir = format!("{FS_ROOT}/{bucket}.blobs");
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(small_key)
+        .body(ByteStream::from_static(b"hello"))
+        .send()
+        .await?;
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(large_key)
+        .body(ByteStream::from(large_content.clone().into_bytes()))
+        .send()
+        .await?;
+
+    // only the large object is stored as a file
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 1);
+
+    for (key, content) in [(small_key, "hello"), (large_key, large_content.as_str())] {
+        let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+        let body = get_object_output.body.collect().await?.into_bytes();
+        assert_eq!(body.as_ref(), content.as_bytes());
+    }
+
+    // the file of a replaced or deleted object is left for the maintenance process as it may still
+    // be read from an older snapshot
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(large_key)
+        .body(ByteStream::from(large_content.clone().into_bytes()))
+        .send()
+        .await?;
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 2);
+
+    delete_object(&context, &bucket, large_key).await?;
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 2);
+
+    // recently orphaned files are kept
+    context.sqlite.run_maintenance().await;
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 2);
+
+    // the sweep runs at most every ten minutes so a new service is used to run it again
+    let modified = std::time::SystemTime::now() - std::time::Duration::from_secs(7200);
+    for entry in fs::read_dir(&blob_dir)? {
+        fs::File::options()
+            .write(true)
+            .open(entry?.path())?
+            .set_modified(modified)?;
+    }
+    TestContext::new(Some(Config {
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await
+    .sqlite
+    .run_maintenance()
+    .await;
+    assert_eq!(fs::read_dir(&blob_dir)?.count(), 0);
+
+    delete_object(&context, &bucket, small_key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    assert!(!Path::new(&blob_dir).exists());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_get_object_if_none_match() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        blob_threshold: Some(0),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-get-object-if-none-match-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    let put_object_output = context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    let e_tag = put_object_output.e_tag().unwrap().to_string();
+
+    // remove the stored value so that any attempt to read it fails
+    for entry in fs::read_dir(format!("{FS_ROOT}/{bucket}.blobs"))? {
+        fs::remove_file(entry?.path())?;
+    }
+
+    let err = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .if_none_match(format!("\"{e_tag}\""))
+        .send()
+        .await
+        .unwrap_err();
+    let response = err.raw_response().unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    assert!(response.headers().contains_key("etag"));
+    assert!(response.headers().contains_key("last-modified"));
+
+    // a different entity tag reads the value
+    assert!(context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .if_none_match("\"0\"")
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_get_object_if_modified_since() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-get-object-if-modified-since-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    let last_modified = *head_object_output.last_modified().unwrap();
+    assert_eq!(last_modified.subsec_nanos(), 0);
+
+    // the returned `Last-Modified` round-trips as an `If-Modified-Since`
+    let err = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .if_modified_since(last_modified)
+        .send()
+        .await
+        .unwrap_err();
+    let response = err.raw_response().unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    assert!(response.headers().contains_key("last-modified"));
+
+    // an earlier time reads the value
+    let body = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .if_modified_since(DateTime::from_secs(last_modified.secs() - 1))
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_with_reader_writer() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-with-reader-writer-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("sample.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    // add a custom index
+    context
+        .sqlite
+        .with_writer(&bucket, |transaction| {
+            transaction.execute(
+                "CREATE INDEX IF NOT EXISTS metadata_size ON metadata (size);",
+                [],
+            )
+        })
+        .await
+        .map_err(S3Error::from)?;
+
+    // run a report
+    let total_size = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            transaction.query_row("SELECT SUM(size) FROM metadata;", [], |row| {
+                row.get::<_, i64>(0)
+            })
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(total_size, 11);
+
+    // a reader cannot write
+    assert!(context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            transaction.execute("DELETE FROM metadata;", [])
+        })
+        .await
+        .is_err());
+
+    // a writer is rejected for a read-only bucket
+    context
+        .sqlite
+        .set_bucket_read_only(&bucket, true)
+        .await
+        .map_err(S3Error::from)?;
+    assert!(context
+        .sqlite
+        .with_writer(&bucket, |transaction| {
+            transaction.execute("DELETE FROM metadata;", [])
+        })
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_import_objects() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-import-objects-{}", Uuid::new_v4());
+    let import_count: usize = 100_000;
+    let put_count: u32 = 1_000;
+
+    create_bucket(&context, &bucket).await?;
+
+    let start = std::time::Instant::now();
+    let imported = context
+        .sqlite
+        .import_objects(
+            &bucket,
+            (0..import_count).map(|i| (format!("import/{i:06}"), i.to_string().into_bytes(), None)),
+        )
+        .await
+        .map_err(S3Error::from)?;
+    let import_elapsed = start.elapsed();
+    assert_eq!(imported, import_count);
+
+    let start = std::time::Instant::now();
+    for i in 0..put_count {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(format!("put/{i:06}"))
+            .body(ByteStream::from(i.to_string().into_bytes()))
+            .send()
+            .await?;
+    }
+    let put_elapsed = start.elapsed();
+
+    // batching the commits makes each imported object far cheaper than a `put_object`, the bench
+    // `import_objects` reports the numbers
+    let import_per_object = import_elapsed / u32::try_from(import_count)?;
+    let put_per_object = put_elapsed / put_count;
+    assert!(
+        import_per_object * 5 < put_per_object,
+        "import_objects: {import_per_object:?} per object, put_object: {put_per_object:?} per object"
+    );
+
+    let count = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            transaction.query_row("SELECT COUNT(*) FROM metadata;", [], |row| {
+                row.get::<_, usize>(0)
+            })
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(count, import_count + usize::try_from(put_count)?);
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key("import/012345")
+        .send()
+        .await?;
+    assert!(get_object_output
+        .e_tag()
+        .unwrap()
+        .contains(&format!("{:x}", Md5::digest(b"12345"))));
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"12345");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_lazy_etags() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        lazy_etags: true,
+        background_maintenance: false,
+        blob_threshold: Some(8),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-lazy-etags-{}", Uuid::new_v4());
+    // one value stored in the database and one as a blob
+    let objects = [("small.txt", "small"), ("large.txt", "larger than eight")];
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .sqlite
+        .import_objects(
+            &bucket,
+            objects.map(|(key, value)| (key.to_string(), value.as_bytes().to_vec(), None)),
+        )
+        .await
+        .map_err(S3Error::from)?;
+
+    for (key, _) in objects {
+        let head_object_output = context
+            .head_object()
+            .bucket(&bucket)
+            .key(key)
+            .send()
+            .await?;
+        assert!(head_object_output.e_tag().is_none());
+    }
+
+    // a directory marker is stored without an ETag too
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("dir/")
+        .send()
+        .await?;
+
+    // the objects without an ETag are listed
+    let list_objects_output = context.list_objects_v2().bucket(&bucket).send().await?;
+    let e_tags = list_objects_output
+        .contents()
+        .unwrap_or_default()
+        .iter()
+        .map(|object| (object.key().unwrap(), object.e_tag()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(e_tags.len(), 3);
+    assert_eq!(e_tags["small.txt"], None);
+    assert_eq!(e_tags["large.txt"], None);
+    assert!(e_tags["dir/"]
+        .unwrap()
+        .contains(&format!("{:x}", Md5::digest(b""))));
+
+    context.sqlite.run_maintenance().await;
+
+    for (key, value) in objects {
+        let head_object_output = context
+            .head_object()
+            .bucket(&bucket)
+            .key(key)
+            .send()
+            .await?;
+        assert!(head_object_output
+            .e_tag()
+            .unwrap()
+            .contains(&format!("{:x}", Md5::digest(value))));
+    }
+
+    let list_objects_output = context.list_objects_v2().bucket(&bucket).send().await?;
+    for object in list_objects_output.contents().unwrap_or_default() {
+        let value = objects
+            .iter()
+            .find(|(key, _)| Some(*key) == object.key())
+            .map_or("", |(_, value)| value);
+        assert!(object
+            .e_tag()
+            .unwrap()
+            .contains(&format!("{:x}", Md5::digest(value))));
+    }
+
+    // the directory marker is not hashed
+    let pending = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            transaction.query_row("SELECT key FROM metadata WHERE md5 IS NULL;", [], |row| {
+                row.get::<_, String>(0)
+            })
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(pending, "dir/");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_no_overwrite() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        no_overwrite: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-no-overwrite-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"goodbye world"))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidRequest") => {}
+        other => panic!("{:?}", other),
+    };
+
+    // the original object is unchanged
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    // the key can be written again once deleted
+    delete_object(&context, &bucket, key).await?;
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"goodbye world"))
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_bucket_encryption() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-bucket-encryption-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    // no configuration has been set
+    assert!(context
+        .get_bucket_encryption()
+        .bucket(&bucket)
+        .send()
+        .await
+        .is_err());
+
+    context
+        .put_bucket_encryption()
+        .bucket(&bucket)
+        .server_side_encryption_configuration(
+            ServerSideEncryptionConfiguration::builder()
+                .rules(
+                    ServerSideEncryptionRule::builder()
+                        .apply_server_side_encryption_by_default(
+                            ServerSideEncryptionByDefault::builder()
+                                .sse_algorithm(ServerSideEncryption::Aes256)
+                                .build(),
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .send()
+        .await?;
+
+    let get_bucket_encryption_output = context
+        .get_bucket_encryption()
+        .bucket(&bucket)
+        .send()
+        .await?;
+    let rules = get_bucket_encryption_output
+        .server_side_encryption_configuration()
+        .unwrap()
+        .rules()
+        .unwrap();
+    assert_eq!(rules.len(), 1);
+    assert_eq!(
+        rules[0]
+            .apply_server_side_encryption_by_default()
+            .unwrap()
+            .sse_algorithm(),
+        Some(&ServerSideEncryption::Aes256)
+    );
+
+    context
+        .delete_bucket_encryption()
+        .bucket(&bucket)
+        .send()
+        .await?;
+
+    assert!(context
+        .get_bucket_encryption()
+        .bucket(&bucket)
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_server_side_encryption_header() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-server-side-encryption-header-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    // no header is returned without a default encryption
+    let put_object_output = context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    assert_eq!(put_object_output.server_side_encryption(), None);
+
+    context
+        .put_bucket_encryption()
+        .bucket(&bucket)
+        .server_side_encryption_configuration(
+            ServerSideEncryptionConfiguration::builder()
+                .rules(
+                    ServerSideEncryptionRule::builder()
+                        .apply_server_side_encryption_by_default(
+                            ServerSideEncryptionByDefault::builder()
+                                .sse_algorithm(ServerSideEncryption::Aes256)
+                                .build(),
+                        )
+                        .build(),
+                )
+                .build(),
+        )
+        .send()
+        .await?;
+
+    let put_object_output = context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    assert_eq!(
+        put_object_output.server_side_encryption(),
+        Some(&ServerSideEncryption::Aes256)
+    );
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    assert_eq!(
+        get_object_output.server_side_encryption(),
+        Some(&ServerSideEncryption::Aes256)
+    );
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        head_object_output.server_side_encryption(),
+        Some(&ServerSideEncryption::Aes256)
+    );
+
+    // the header follows the default encryption as it changes
+    context
+        .delete_bucket_encryption()
+        .bucket(&bucket)
+        .send()
+        .await?;
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    assert_eq!(get_object_output.server_side_encryption(), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_bucket_website_index_document() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        website: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-bucket-website-index-document-{}", Uuid::new_v4());
+    let content = "<html></html>";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_bucket_website()
+        .bucket(&bucket)
+        .website_configuration(
+            WebsiteConfiguration::builder()
+                .index_document(IndexDocument::builder().suffix("index.html").build())
+                .error_document(ErrorDocument::builder().key("error.html").build())
+                .build(),
+        )
+        .send()
+        .await?;
+
+    let get_bucket_website_output = context.get_bucket_website().bucket(&bucket).send().await?;
+    assert_eq!(
+        get_bucket_website_output.index_document().unwrap().suffix(),
+        Some("index.html")
+    );
+    assert_eq!(
+        get_bucket_website_output.error_document().unwrap().key(),
+        Some("error.html")
+    );
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("docs/index.html")
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key("docs/")
+        .send()
+        .await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    // a missing key is a 404 until the error document exists
+    match context
+        .get_object()
+        .bucket(&bucket)
+        .key("missing.html")
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("NoSuchKey") => {
+            assert_eq!(error_response(&err).0, Some(404));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    let error_content = "<html>not found</html>";
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("error.html")
+        .content_type("text/html")
+        .body(ByteStream::from_static(error_content.as_bytes()))
+        .send()
+        .await?;
+
+    // the same layer as the binary in front of the service
+    let mut service = tower::ServiceBuilder::new()
+        .layer(tower::util::MapResponseLayer::new(
+            Sqlite::map_website_response,
+        ))
+        .service(context.service.clone());
+
+    // the error document is served in place of a missing key with a 404 status
+    for key in ["missing.html", "missing/"] {
+        let presigned = context
+            .get_object()
+            .bucket(&bucket)
+            .key(key)
+            .presigned(PresigningConfig::expires_in(
+                std::time::Duration::from_secs(60),
+            )?)
+            .await?;
+        let request = presigned_request("GET", &presigned.uri().to_string(), Body::empty())?;
+        let response = service.call(request).await?;
+
+        assert_eq!(response.status().as_u16(), 404);
+        assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+        assert!(response
+            .headers()
+            .contains_key("x-s3ite-error-document")
+            .not());
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        assert_eq!(body.as_ref(), error_content.as_bytes());
+    }
+
+    // an existing key is not affected by the layer
+    let presigned = context
+        .get_object()
+        .bucket(&bucket)
+        .key("docs/")
+        .presigned(PresigningConfig::expires_in(
+            std::time::Duration::from_secs(60),
+        )?)
+        .await?;
+    let request = presigned_request("GET", &presigned.uri().to_string(), Body::empty())?;
+    let response = service.call(request).await?;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_verify_on_read() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        verify_on_read: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-verify-on-read-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    // corrupt the stored value without updating its checksum
+    context
+        .sqlite
+        .with_writer(&bucket, |transaction| {
+            transaction.execute("UPDATE data SET value = zeroblob(11);", [])
+        })
+        .await
+        .map_err(S3Error::from)?;
+
+    assert!(context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await
+        .is_err());
+    assert!(context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .range("bytes=0-4")
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_shared_cache() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        shared_cache: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-shared-cache-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    // the bucket database is opened at the same path through the URI filename
+    assert!(fs::metadata(format!("{FS_ROOT}/{bucket}.sqlite3")).is_ok());
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    delete_object(&context, &bucket, key).await?;
+    delete_bucket(&context, &bucket).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_checkpoint_on_close() -> Result<()> {
+    let _guard = serial().await;
+
+    for checkpoint_on_close in [true, false] {
+        let context = TestContext::new(Some(Config {
+            checkpoint_on_close,
+            ..Default::default()
+        }))
+        .await;
+
+        let bucket = format!("test-checkpoint-on-close-{}", Uuid::new_v4());
+
+        create_bucket(&context, &bucket).await?;
+
+        // closing any connection of the pool must not checkpoint, not only the first one it opened
+        let no_checkpoint_on_close = query_pool_connections(&context, &bucket, |transaction| {
+            transaction.db_config(DbConfig::SQLITE_DBCONFIG_NO_CKPT_ON_CLOSE)
+        })
+        .await?;
+        assert!(no_checkpoint_on_close
+            .iter()
+            .all(|no_checkpoint_on_close| *no_checkpoint_on_close == checkpoint_on_close.not()));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_extra_pragmas() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        extra_pragmas: vec!["cell_size_check=true".to_string()],
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-extra-pragmas-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    // the pragmas apply to every connection of the pool, not only the first one it opened
+    let cell_size_check = query_pool_connections(&context, &bucket, |transaction| {
+        transaction.query_row("PRAGMA cell_size_check;", [], |row| row.get::<_, bool>(0))
+    })
+    .await?;
+    assert!(cell_size_check
+        .iter()
+        .all(|cell_size_check| *cell_size_check));
+
+    for extra_pragma in [
+        "user_version=1",
+        "cell_size_check=true; DROP TABLE data",
+        "cell_size_check",
+    ] {
+        let config = Config {
+            extra_pragmas: vec![extra_pragma.to_string()],
+            ..Default::default()
+        };
+        match config.validate() {
+            Err(err) if format!("{err:?}").contains("extra_pragmas") => {}
+            other => panic!("{:?}", other),
+        }
+        assert!(config.to_sql(None).contains(extra_pragma).not());
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_journal_mode_transition() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-journal-mode-transition-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let saved_path = format!("{}/{bucket}.sqlite3", env!("CARGO_TARGET_TMPDIR"));
+
+    // create a populated WAL database and keep a copy of it once the service is dropped
+    {
+        let context = TestContext::new(None).await;
+        create_bucket(&context, &bucket).await?;
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+        for suffix in ["", "-wal"] {
+            fs::copy(
+                format!("{FS_ROOT}/{bucket}.sqlite3{suffix}"),
+                format!("{saved_path}{suffix}"),
+            )?;
+        }
+    }
+
+    fs::create_dir_all(FS_ROOT)?;
+    for suffix in ["", "-wal"] {
+        fs::rename(
+            format!("{saved_path}{suffix}"),
+            format!("{FS_ROOT}/{bucket}.sqlite3{suffix}"),
+        )?;
+    }
+
+    let context = TestContext::new(Some(Config {
+        sqlite: Pragmas {
+            journal_mode: JournalMode::DELETE,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .await;
+
+    let journal_mode = context
+        .sqlite
+        .with_reader(&bucket, |transaction| {
+            transaction.query_row("PRAGMA journal_mode;", [], |row| row.get::<_, String>(0))
+        })
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(journal_mode, "delete");
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_search_keys() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-search-keys-{}", Uuid::new_v4());
+
+    // a bucket level configuration requires the bucket to exist
+    create_bucket_file(&bucket).await?;
+
+    let context = TestContext::new(Some(Config {
+        buckets: HashMap::from([(
+            bucket.clone(),
+            Bucket {
+                key_search: Some(true),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    }))
+    .await;
+
+    for key in [
+        "logs/2023/annual-report-final.csv",
+        "logs/2023/summary.csv",
+        "images/report.png",
+        "images/photo.png",
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    let search_keys = |query: &'static str| {
+        let sqlite = context.sqlite.clone();
+        let bucket = bucket.clone();
+        async move {
+            Ok::<_, S3Error>(
+                sqlite
+                    .search_keys(&bucket, query, 10)
+                    .await
+                    .map_err(S3Error::from)?
+                    .into_iter()
+                    .map(|key_size| key_size.key)
+                    .collect::<Vec<_>>(),
+            )
+        }
+    };
+
+    // an interior substring of the key
+    assert_eq!(
+        search_keys("report").await?,
+        vec!["images/report.png", "logs/2023/annual-report-final.csv"]
+    );
+    assert_eq!(
+        search_keys("nual-rep").await?,
+        vec!["logs/2023/annual-report-final.csv"]
+    );
+
+    // the index follows deletes and renames
+    context
+        .delete_object()
+        .bucket(&bucket)
+        .key("images/report.png")
+        .send()
+        .await?;
+    context
+        .sqlite
+        .rename_object(
+            &bucket,
+            "logs/2023/summary.csv",
+            "logs/2023/overview.csv",
+            false,
+        )
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(
+        search_keys("report").await?,
+        vec!["logs/2023/annual-report-final.csv"]
+    );
+    assert!(search_keys("summary").await?.is_empty());
+    assert_eq!(
+        search_keys("overview").await?,
+        vec!["logs/2023/overview.csv"]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_bucket_concurrency_limit() -> Result<()> {
+    let _guard = serial().await;
+
+    let slow_bucket = format!("test-bucket-concurrency-limit-slow-{}", Uuid::new_v4());
+    let created_bucket = format!("test-bucket-concurrency-limit-created-{}", Uuid::new_v4());
+    let fast_bucket = format!("test-bucket-concurrency-limit-fast-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    // the slow bucket exists on startup while the other limited bucket is created at runtime
+    create_bucket_file(&slow_bucket).await?;
+
+    let context = TestContext::new(Some(Config {
+        buckets: HashMap::from([
+            (
+                slow_bucket.clone(),
+                Bucket {
+                    concurrency_limit: Some(1),
+                    ..Default::default()
+                },
+            ),
+            (
+                created_bucket.clone(),
+                Bucket {
+                    concurrency_limit: Some(1),
+                    ..Default::default()
+                },
+            ),
+        ]),
+        ..Default::default()
+    }))
+    .await;
+
+    create_bucket(&context, &created_bucket).await?;
+    create_bucket(&context, &fast_bucket).await?;
+
+    for bucket in [&slow_bucket, &created_bucket, &fast_bucket] {
+        context
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    for limited_bucket in [&slow_bucket, &created_bucket] {
+        // hold the only permit of the limited bucket
+        let sqlite = context.sqlite.clone();
+        let limited_bucket_clone = limited_bucket.clone();
+        let handle = tokio::spawn(async move {
+            sqlite
+                .with_reader(&limited_bucket_clone, |_| {
+                    std::thread::sleep(std::time::Duration::from_millis(500));
+                    Ok(())
+                })
+                .await
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        // the other bucket proceeds freely
+        let start = std::time::Instant::now();
+        context
+            .head_object()
+            .bucket(&fast_bucket)
+            .key(key)
+            .send()
+            .await?;
+        assert!(start.elapsed() < std::time::Duration::from_millis(250));
+
+        // the limited bucket waits for the permit
+        let start = std::time::Instant::now();
+        context
+            .head_object()
+            .bucket(limited_bucket)
+            .key(key)
+            .send()
+            .await?;
+        assert!(start.elapsed() >= std::time::Duration::from_millis(250));
+
+        handle.await?.map_err(S3Error::from)?;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_default_metadata() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-default-metadata-{}", Uuid::new_v4());
+
+    // a bucket level configuration requires the bucket to exist
+    create_bucket_file(&bucket).await?;
+
+    let context = TestContext::new(Some(Config {
+        buckets: HashMap::from([(
+            bucket.clone(),
+            Bucket {
+                default_metadata: Some(HashMap::from([(
+                    "cache-policy".to_string(),
+                    "public".to_string(),
+                )])),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    }))
+    .await;
+
+    // the default is applied when the client omits the metadata
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("default.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key("default.txt")
+        .send()
+        .await?;
+    assert_eq!(
+        head_object_output
+            .metadata()
+            .and_then(|metadata| metadata.get("cache-policy"))
+            .map(String::as_str),
+        Some("public")
+    );
+
+    // the client value wins over the default
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("private.txt")
+        .metadata("cache-policy", "private")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key("private.txt")
+        .send()
+        .await?;
+    assert_eq!(
+        head_object_output
+            .metadata()
+            .and_then(|metadata| metadata.get("cache-policy"))
+            .map(String::as_str),
+        Some("private")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_allowed_operations() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        allowed_operations: HashMap::from([(
+            Credentials::for_tests().access_key_id().to_string(),
+            vec![
+                "CreateBucket".to_string(),
+                "Get*".to_string(),
+                "Head*".to_string(),
+                "List*".to_string(),
+            ],
+        )]),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-allowed-operations-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key("sample.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("AccessDenied") => {}
+        other => panic!("{:?}", other),
+    };
+
+    let list_objects_output = context.list_objects_v2().bucket(&bucket).send().await?;
+    assert_eq!(list_objects_output.key_count(), 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_credentials() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        allowed_operations: HashMap::from([(
+            Credentials::for_tests().access_key_id().to_string(),
+            vec![
+                "CreateBucket".to_string(),
+                "PutObject".to_string(),
+                "ListObjects".to_string(),
+            ],
+        )]),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-list-objects-credentials-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in ["a.txt", "b.txt", "c.txt"] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    // ListObjects does not require ListObjectsV2 to be allowed
+    let mut keys = Vec::new();
+    let mut marker = None;
+    loop {
+        let list_objects_output = context
+            .list_objects()
+            .bucket(&bucket)
+            .max_keys(1)
+            .set_marker(marker)
+            .send()
+            .await?;
+        keys.extend(
+            list_objects_output
+                .contents()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|object| object.key().map(ToString::to_string)),
+        );
+        if list_objects_output.is_truncated().not() {
+            break;
+        }
+        marker = list_objects_output.next_marker().map(ToString::to_string);
+    }
+    assert_eq!(keys, vec!["a.txt", "b.txt", "c.txt"]);
+
+    match context.list_objects_v2().bucket(&bucket).send().await {
+        Err(err) if format!("{err:?}").contains("AccessDenied") => {}
+        other => panic!("{:?}", other),
+    };
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_max_keys() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-objects-max-keys-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key("sample.txt")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    match context
+        .list_objects_v2()
+        .bucket(&bucket)
+        .max_keys(-1)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+        other => panic!("{:?}", other),
+    };
+
+    match context
+        .list_objects()
+        .bucket(&bucket)
+        .max_keys(-1)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("InvalidArgument") => {}
+        other => panic!("{:?}", other),
+    };
+
+    // zero returns no keys but reports that there are more
+    let list_objects_output = context
+        .list_objects_v2()
+        .bucket(&bucket)
+        .max_keys(0)
+        .send()
+        .await?;
+    assert_eq!(list_objects_output.key_count(), 0);
+    assert!(list_objects_output.is_truncated());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_list_objects_v2_credentials() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-list-objects-v2-credentials-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    for key in ["a.txt", "b.txt", "c.txt"] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+    }
+
+    // the continuation token is resumed by the same access key which started the listing
+    let mut keys = Vec::new();
+    let mut continuation_token = None;
+    loop {
+        let list_objects_output = context
+            .list_objects_v2()
+            .bucket(&bucket)
+            .max_keys(1)
+            .set_continuation_token(continuation_token)
+            .send()
+            .await?;
+        keys.extend(
+            list_objects_output
+                .contents()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|object| object.key().map(ToString::to_string)),
+        );
+        if list_objects_output.is_truncated().not() {
+            break;
+        }
+        continuation_token = list_objects_output
+            .next_continuation_token()
+            .map(ToString::to_string);
+    }
+    assert_eq!(keys, vec!["a.txt", "b.txt", "c.txt"]);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_expected_bucket_owner() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        owner_id: Some("111122223333".to_string()),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-expected-bucket-owner-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .expected_bucket_owner("111122223333")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    match context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .expected_bucket_owner("444455556666")
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("AccessDenied") => {}
+        other => panic!("{:?}", other),
+    };
+
+    match context
+        .list_objects_v2()
+        .bucket(&bucket)
+        .expected_bucket_owner("444455556666")
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("AccessDenied") => {}
+        other => panic!("{:?}", other),
+    };
+
+    let list_buckets_output = context.list_buckets().send().await?;
+    assert_eq!(
+        list_buckets_output.owner().and_then(|owner| owner.id()),
+        Some("111122223333")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_pool_connection_pragmas() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        sqlite: Pragmas {
+            busy_timeout: 1234,
+            synchronous: Synchronous::FULL,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-pool-connection-pragmas-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    // every connection of the pool is configured, not only the first one it opened
+    let busy_timeouts = query_pool_connections(&context, &bucket, |transaction| {
+        transaction.query_row("PRAGMA busy_timeout;", [], |row| row.get::<_, u32>(0))
+    })
+    .await?;
+    assert!(busy_timeouts
+        .iter()
+        .all(|busy_timeout| *busy_timeout == 1234));
+
+    // writes on any connection have the configured durability. `FULL` is reported as 2
+    let synchronous = query_pool_connections(&context, &bucket, |transaction| {
+        transaction.query_row("PRAGMA synchronous;", [], |row| row.get::<_, u8>(0))
+    })
+    .await?;
+    assert!(synchronous.iter().all(|synchronous| *synchronous == 2));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_busy_slow_down() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        busy_retries: 0,
+        sqlite: Pragmas {
+            busy_timeout: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-busy-slow-down-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    // hold the write lock of the bucket from another connection
+    let connection = rusqlite::Connection::open(format!("{FS_ROOT}/{bucket}.sqlite3"))?;
+    connection.execute_batch("BEGIN EXCLUSIVE;")?;
+
+    let started = std::time::Instant::now();
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("SlowDown") => {}
+        other => panic!("{:?}", other),
+    };
+    // the configured busy_timeout of zero fails at once rather than after the default of 5 seconds
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+    connection.execute_batch("ROLLBACK;")?;
+    drop(connection);
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_pool_stats() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        pool_warning_waiting: 1,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-pool-stats-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(pool_stats.waiting, 0);
+
+    // flood the bucket with more slow requests than it has connections
+    let handles = (0..pool_stats.max_size * 2)
+        .map(|_| {
+            let sqlite = context.sqlite.clone();
+            let bucket = bucket.clone();
+            tokio::spawn(async move {
+                sqlite
+                    .with_reader(&bucket, |_| {
+                        std::thread::sleep(std::time::Duration::from_millis(200));
+                        Ok(())
+                    })
+                    .await
+            })
+        })
+        .collect::<Vec<_>>();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(pool_stats.available, 0);
+    assert!(pool_stats.waiting > 0);
+
+    for handle in handles {
+        handle.await?.map_err(S3Error::from)?;
+    }
+
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(pool_stats.waiting, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_bucket_quiesced() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-bucket-quiesced-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let sqlite = context.sqlite.clone();
+    let bucket_clone = bucket.clone();
+    let handle = tokio::spawn(async move {
+        sqlite
+            .with_bucket_quiesced(&bucket_clone, |connection| {
+                std::thread::sleep(std::time::Duration::from_millis(1000));
+                connection.execute_batch("VACUUM;")
+            })
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    match context.get_object().bucket(&bucket).key(key).send().await {
+        Err(err) if format!("{err:?}").contains("SlowDown") => {
+            assert_eq!(error_response(&err).0, Some(503));
+        }
+        other => panic!("{:?}", other),
+    };
+
+    // the library writes are held off like the S3 operations
+    match context
+        .sqlite
+        .rename_object(&bucket, key, "renamed.txt", false)
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("SlowDown") => {}
+        other => panic!("{:?}", other),
+    };
+    match context
+        .sqlite
+        .import_objects(
+            &bucket,
+            [("imported.txt".to_string(), b"hello".to_vec(), None)],
+        )
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("SlowDown") => {}
+        other => panic!("{:?}", other),
+    };
+
+    handle.await?.map_err(S3Error::from)?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_region() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-region-{}", Uuid::new_v4());
+
+    create_bucket(&context, &bucket).await?;
+
+    let get_bucket_location_output = context.get_bucket_location().bucket(&bucket).send().await?;
+    assert_eq!(
+        get_bucket_location_output.location_constraint(),
+        Some(&BucketLocationConstraint::from(REGION))
+    );
+
+    let cfg = CreateBucketConfiguration::builder()
+        .location_constraint(BucketLocationConstraint::from("eu-west-1"))
+        .build();
+    match context
+        .create_bucket()
+        .create_bucket_configuration(cfg)
+        .bucket(format!("test-region-{}", Uuid::new_v4()))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("IllegalLocationConstraintException") => {}
+        other => panic!("{:?}", other),
+    };
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_busy_retries() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        busy_retries: 5,
+        sqlite: Pragmas {
+            busy_timeout: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-busy-retries-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    // hold the write lock of the bucket from another connection for less time than the retries take
+    let connection = rusqlite::Connection::open(format!("{FS_ROOT}/{bucket}.sqlite3"))?;
+    connection.execute_batch("BEGIN EXCLUSIVE;")?;
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        connection.execute_batch("ROLLBACK;")
+    });
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+    release.join().unwrap()?;
+
+    let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+    let body = get_object_output.body.collect().await?.into_bytes();
+    assert_eq!(body.as_ref(), b"hello world");
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_head_directory_marker() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        directory_content_type: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-head-directory-marker-{}", Uuid::new_v4());
+    let key = "folder/";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b""))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(head_object_output.content_length(), 0);
+    assert!(head_object_output
+        .e_tag()
+        .unwrap()
+        .contains("d41d8cd98f00b204e9800998ecf8427e"));
+    assert_eq!(
+        head_object_output.content_type(),
+        Some("application/x-directory")
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_track_created_at() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        track_created_at: true,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-track-created-at-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    let created_at = head_object_output
+        .metadata()
+        .and_then(|metadata| metadata.get("s3ite-created-at"))
+        .cloned()
+        .unwrap();
+    let last_modified = head_object_output.last_modified().unwrap().secs();
+
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    // overwriting the object updates last_modified but keeps created_at
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"goodbye world"))
+        .send()
+        .await?;
+
+    let head_object_output = context
+        .head_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?;
+    assert_eq!(
+        head_object_output
+            .metadata()
+            .and_then(|metadata| metadata.get("s3ite-created-at")),
+        Some(&created_at)
+    );
+    assert!(head_object_output.last_modified().unwrap().secs() > last_modified);
+
+    Ok(())
+}
+
+#[test]
+fn test_durable_bucket() {
+    let config = Config {
+        sqlite: Pragmas {
+            journal_mode: JournalMode::DELETE,
+            synchronous: Synchronous::OFF,
+            ..Default::default()
+        },
+        buckets: HashMap::from([(
+            "critical".to_string(),
+            Bucket {
+                durable: Some(true),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+
+    assert_eq!(config.journal_mode(Some("critical")), JournalMode::WAL);
+    assert_eq!(config.synchronous(Some("critical")), Synchronous::FULL);
+    assert_eq!(config.journal_mode(Some("other")), JournalMode::DELETE);
+    assert_eq!(config.synchronous(Some("other")), Synchronous::OFF);
+    assert_eq!(config.journal_mode(None), JournalMode::DELETE);
+}
+
+#[test]
+fn test_dangerous_pragmas() {
+    let mut config = Config {
+        sqlite: Pragmas {
+            journal_mode: JournalMode::OFF,
+            synchronous: Synchronous::OFF,
+            ..Default::default()
+        },
+        buckets: HashMap::from([(
+            "critical".to_string(),
+            Bucket {
+                durable: Some(true),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+
+    // the durable bucket is not affected by the service level pragmas
+    let warnings = config.pragma_warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("service: journal_mode=OFF with synchronous=OFF"));
+
+    config.buckets.clear();
+    assert!(config.validate().is_ok());
+
+    config.strict_pragmas = true;
+    match config.validate() {
+        Err(err) if format!("{err:?}").contains("journal_mode=OFF with synchronous=OFF") => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn test_merge_buckets_dir() -> Result<()> {
+    // a relative `buckets_dir` is resolved against the working directory of the process
+    let absolute =
+        Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("buckets-{}", Uuid::new_v4()));
+    let relative = Path::new("target").join(format!("buckets-{}", Uuid::new_v4()));
+
+    for buckets_dir in [&absolute, &relative] {
+        fs::create_dir_all(buckets_dir)?;
+        fs::write(
+            buckets_dir.join("archive.yaml"),
+            "read_only: true\nsqlite:\n  cache_size: 1024\n",
+        )?;
+        fs::write(buckets_dir.join("uploads.yaml"), "no_overwrite: true\n")?;
+        fs::write(buckets_dir.join("notes.txt"), "read_only: true\n")?;
+
+        let mut config = Config {
+            buckets_dir: Some(buckets_dir.clone()),
+            buckets: HashMap::from([
+                (
+                    "archive".to_string(),
+                    Bucket {
+                        no_overwrite: Some(true),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "logs".to_string(),
+                    Bucket {
+                        read_only: Some(true),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let merged = config.merge_buckets_dir();
+        fs::remove_dir_all(buckets_dir)?;
+        merged.unwrap();
+
+        let mut buckets = config.buckets.keys().cloned().collect::<Vec<_>>();
+        buckets.sort();
+        assert_eq!(buckets, ["archive", "logs", "uploads"]);
+
+        // the per-bucket file replaces the configuration of the main file
+        let archive = &config.buckets["archive"];
+        assert_eq!(archive.read_only, Some(true));
+        assert_eq!(archive.no_overwrite, None);
+        assert_eq!(
+            archive.sqlite.as_ref().and_then(|sqlite| sqlite.cache_size),
+            Some(1024)
+        );
+        assert_eq!(config.buckets["logs"].read_only, Some(true));
+        assert_eq!(config.buckets["uploads"].no_overwrite, Some(true));
+        assert!(config.read_only(Some("archive")));
+        assert!(config.no_overwrite(Some("uploads")));
+    }
+
+    // a missing directory is an error rather than an empty set of buckets
+    let mut config = Config {
+        buckets_dir: Some(absolute),
+        ..Default::default()
+    };
+    assert!(config.merge_buckets_dir().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_read_only_conflicts() {
+    fs::create_dir_all(FS_ROOT).unwrap();
+    let mut config = Config {
+        root: FS_ROOT.into(),
+        sqlite: Pragmas {
+            cache_size: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(config.validate().is_ok());
+
+    config.read_only = true;
+    config.import_mode = true;
+    match config.validate() {
+        Err(err)
+            if format!("{err:?}").contains("read_only cannot be combined with import_mode") => {}
+        other => panic!("{:?}", other),
+    }
+
+    // a bucket only conflicts if it is read-only once the service level flag is applied
+    config.import_mode = false;
+    config.buckets = HashMap::from([(
+        "archive".to_string(),
+        Bucket {
+            no_overwrite: Some(true),
+            ..Default::default()
+        },
+    )]);
+    match config.validate() {
+        Err(err)
+            if format!("{err:?}")
+                .contains("archive: read_only cannot be combined with no_overwrite") => {}
+        other => panic!("{:?}", other),
+    }
+
+    config.buckets.get_mut("archive").unwrap().read_only = Some(false);
+    match config.validate() {
+        Err(err)
+            if format!("{err:?}")
+                .contains("read_only cannot be combined")
+                .not() => {}
+        other => panic!("{:?}", other),
+    }
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_adopt_bare_buckets() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-adopt-bare-buckets-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "hello world";
+
+    // a database created by another tool without the s3ite tables
+    fs::create_dir_all(FS_ROOT)?;
+    let path = format!("{FS_ROOT}/{bucket}.sqlite3");
+    rusqlite::Connection::open(&path)?.execute_batch("CREATE TABLE other (id INTEGER);")?;
+
+    let config = Config {
+        root: FS_ROOT.into(),
+        ..Default::default()
+    };
+    match Sqlite::new(&config).await {
+        Err(err) if format!("{err:?}").contains("adopt_bare_buckets") => {}
+        other => {
+            fs::remove_file(&path)?;
+            panic!("{:?}", other.map(|_| ()))
+        }
+    }
+
+    let context = TestContext::new(Some(Config {
+        adopt_bare_buckets: true,
+        ..Default::default()
+    }))
+    .await;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    let body = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes();
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_idle_timeout() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        idle_timeout: Some(1),
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-idle-timeout-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = "hello world";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from_static(content.as_bytes()))
+        .send()
+        .await?;
+
+    // a bucket accessed within the timeout is kept open
+    context.sqlite.run_maintenance().await;
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert!(pool_stats.size > 0);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    context.sqlite.run_maintenance().await;
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert_eq!(pool_stats.size, 0);
+
+    let body = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .send()
+        .await?
+        .body
+        .collect()
+        .await?
+        .into_bytes();
+    assert_eq!(body.as_ref(), content.as_bytes());
+
+    let pool_stats = context
+        .sqlite
+        .pool_stats(&bucket)
+        .await
+        .map_err(S3Error::from)?;
+    assert!(pool_stats.size > 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_presigned_urls() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-presigned-urls-{}", Uuid::new_v4());
+    let key = "sample.txt";
+    let content = b"hello world";
+    let content_disposition = "attachment; filename=\"download.txt\"";
+
+    create_bucket(&context, &bucket).await?;
+
+    let presigning_config = PresigningConfig::expires_in(std::time::Duration::from_secs(60))?;
+
+    let presigned = context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .presigned(presigning_config.clone())
+        .await?;
+    let response = send_presigned(
+        &context,
+        "PUT",
+        &presigned.uri().to_string(),
+        Body::from(&content[..]),
+    )
+    .await?;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // the overrides embedded in the presigned URL are applied to the response
+    let presigned = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .response_content_disposition(content_disposition)
+        .response_content_type("text/plain")
+        .presigned(presigning_config)
+        .await?;
+    let response =
+        send_presigned(&context, "GET", &presigned.uri().to_string(), Body::empty()).await?;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get(CONTENT_DISPOSITION).unwrap(),
+        content_disposition
+    );
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/plain"
+    );
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    assert_eq!(body.as_ref(), content);
+
+    // an expired presigned URL is rejected
+    let presigned = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .presigned(PresigningConfig::expires_in(
+            std::time::Duration::from_secs(1),
+        )?)
+        .await?;
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    let response =
+        send_presigned(&context, "GET", &presigned.uri().to_string(), Body::empty()).await?;
+    assert_eq!(response.status().as_u16(), 403);
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_vacuum_freelist_percent() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        vacuum_freelist_percent: Some(50),
+        background_maintenance: false,
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-vacuum-freelist-percent-{}", Uuid::new_v4());
+    let path = format!("{FS_ROOT}/{bucket}.sqlite3");
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .sqlite
+        .import_objects(
+            &bucket,
+            (0..100).map(|i| (format!("{i:03}"), vec![0u8; 65_536], None)),
+        )
+        .await
+        .map_err(S3Error::from)?;
+    context.sqlite.run_maintenance().await;
+    let size = fs::metadata(&path)?.len();
+
+    context
+        .sqlite
+        .with_writer(&bucket, |transaction| {
+            transaction.execute("DELETE FROM data WHERE key != '000';", [])
+        })
+        .await
+        .map_err(S3Error::from)?;
+    context.sqlite.run_maintenance().await;
+
+    // incremental vacuum alone reclaims only 100 pages each pass
+    assert!(fs::metadata(&path)?.len() < size / 4);
+
+    context
+        .get_object()
+        .bucket(&bucket)
+        .key("000")
+        .send()
+        .await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_content_disposition() -> Result<()> {
+    let _guard = serial().await;
+
+    let bucket = format!("test-content-disposition-{}", Uuid::new_v4());
+
+    // a bucket level configuration requires the bucket to exist
+    create_bucket_file(&bucket).await?;
+
+    let context = TestContext::new(Some(Config {
+        buckets: HashMap::from([(
+            bucket.clone(),
+            Bucket {
+                content_disposition: Some("attachment".to_string()),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    }))
+    .await;
+
+    for (key, expected) in [
+        (
+            "reports/2023/report.pdf",
+            "attachment; filename=\"report.pdf\"",
+        ),
+        (
+            "résumé \"final\".txt",
+            "attachment; filename=\"r_sum_ \\\"final\\\".txt\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.txt",
+        ),
+    ] {
+        context
+            .put_object()
+            .bucket(&bucket)
+            .key(key)
+            .body(ByteStream::from_static(b"hello world"))
+            .send()
+            .await?;
+
+        let get_object_output = context.get_object().bucket(&bucket).key(key).send().await?;
+        assert_eq!(get_object_output.content_disposition(), Some(expected));
+
+        let head_object_output = context.head_object().bucket(&bucket).key(key).send().await?;
+        assert_eq!(head_object_output.content_disposition(), Some(expected));
+    }
+
+    // the disposition requested by the client takes precedence
+    let get_object_output = context
+        .get_object()
+        .bucket(&bucket)
+        .key("reports/2023/report.pdf")
+        .response_content_disposition("inline")
+        .send()
+        .await?;
+    assert_eq!(get_object_output.content_disposition(), Some("inline"));
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_max_object_size() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(Some(Config {
+        max_object_size: Some(100_000),
+        ..Default::default()
+    }))
+    .await;
+
+    let bucket = format!("test-max-object-size-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from(vec![0u8; 100_000]))
+        .send()
+        .await?;
+
+    // a declared length over the limit is rejected
+    match context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .body(ByteStream::from(vec![0u8; 100_001]))
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("EntityTooLarge") => {
+            assert_eq!(error_response(&err).0, Some(400));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // a chunked upload without a length is aborted once it exceeds the limit rather than read in full
+    let chunks = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let chunks_clone = chunks.clone();
+    let body = Body::wrap_stream(futures::stream::iter(0..100).map(move |_| {
+        chunks_clone.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Ok::<_, std::io::Error>(vec![0u8; 65_536])
+    }));
+    assert!(body.size_hint().exact().is_none());
+
+    let presigned = context
+        .put_object()
+        .bucket(&bucket)
+        .key("chunked.txt")
+        .presigned(PresigningConfig::expires_in(
+            std::time::Duration::from_secs(60),
+        )?)
+        .await?;
+    let response = send_presigned(&context, "PUT", &presigned.uri().to_string(), body).await?;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(chunks.load(std::sync::atomic::Ordering::SeqCst) < 100);
+
+    assert!(context
+        .head_object()
+        .bucket(&bucket)
+        .key("chunked.txt")
+        .send()
+        .await
+        .is_err());
+
+    // parts each within the limit are rejected once they are combined into a larger object
+    let multipart_key = "multipart.txt";
+    let create_multipart_upload_output = context
+        .create_multipart_upload()
+        .bucket(&bucket)
+        .key(multipart_key)
+        .send()
+        .await?;
+    let upload_id = create_multipart_upload_output.upload_id().unwrap();
+
+    let mut upload = CompletedMultipartUpload::builder();
+    for part_number in 1..=2 {
+        let upload_part_output = context
+            .upload_part()
+            .bucket(&bucket)
+            .key(multipart_key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(vec![0u8; 60_000]))
+            .send()
+            .await?;
+        upload = upload.parts(
+            CompletedPart::builder()
+                .e_tag(upload_part_output.e_tag().unwrap_or_default())
+                .part_number(part_number)
+                .build(),
+        );
+    }
+
+    match context
+        .complete_multipart_upload()
+        .bucket(&bucket)
+        .key(multipart_key)
+        .multipart_upload(upload.build())
+        .upload_id(upload_id)
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("EntityTooLarge") => {
+            assert_eq!(error_response(&err).0, Some(400));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    assert!(context
+        .head_object()
+        .bucket(&bucket)
+        .key(multipart_key)
+        .send()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_response_headers() -> Result<()> {
+    let _guard = serial().await;
+    let config = Config {
+        response_headers: HashMap::from([
+            ("X-Content-Type-Options".to_string(), "nosniff".to_string()),
+            ("Content-Type".to_string(), "text/html".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let context = TestContext::new(Some(config.clone())).await;
+
+    let bucket = format!("test-response-headers-{}", Uuid::new_v4());
+    let key = "sample.txt";
+
+    create_bucket(&context, &bucket).await?;
+
+    context
+        .put_object()
+        .bucket(&bucket)
+        .key(key)
+        .content_type("text/plain")
+        .body(ByteStream::from_static(b"hello world"))
+        .send()
+        .await?;
+
+    // the same layer as the binary in front of the service
+    let response_headers = config.response_header_map();
+    let mut service = tower::ServiceBuilder::new()
+        .layer(tower::util::MapResponseLayer::new(
+            move |mut response: Response<_>| {
+                for (name, value) in &response_headers {
+                    if response.headers().contains_key(name).not() {
+                        response.headers_mut().insert(name, value.clone());
+                    }
+                }
+                response
+            },
+        ))
+        .service(context.service.clone());
+
+    let presigned = context
+        .get_object()
+        .bucket(&bucket)
+        .key(key)
+        .presigned(PresigningConfig::expires_in(
+            std::time::Duration::from_secs(60),
+        )?)
+        .await?;
+    let request = presigned_request("GET", &presigned.uri().to_string(), Body::empty())?;
+    let response = service.call(request).await?;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("x-content-type-options").unwrap(),
+        "nosniff"
+    );
+    // a header set by the response is kept
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/plain"
+    );
+
+    let config = Config {
+        response_headers: HashMap::from([("X-Invalid".to_string(), "line\nbreak".to_string())]),
+        ..Default::default()
+    };
+    match config.validate() {
+        Err(err) if format!("{err:?}").contains("response_headers") => {}
+        other => panic!("{:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+#[tracing::instrument]
+async fn test_delete_objects_empty() -> Result<()> {
+    let _guard = serial().await;
+    let context = TestContext::new(None).await;
+
+    let bucket = format!("test-delete-objects-empty-{}", Uuid::new_v4());
+    let bucket = bucket.as_str();
+    context.create_bucket().bucket(bucket).send().await?;
+
+    let key = "sample.txt";
+    context
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .body(ByteStream::from_static(b"hello"))
+        .send()
+        .await?;
+
+    // an empty delete is rejected rather than reaching the database
+    match context
+        .delete_objects()
+        .bucket(bucket)
+        .delete(Delete::builder().set_objects(Some(vec![])).build())
+        .send()
+        .await
+    {
+        Err(err) if format!("{err:?}").contains("MalformedXML") => {
+            assert_eq!(error_response(&err).0, Some(400));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // the bucket is still usable afterwards
+    context.head_object().bucket(bucket).key(key).send().await?;
+
+    context
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+    context.delete_bucket().bucket(bucket).send().await?;
+
+    Ok(())
+}